@@ -1,124 +1,213 @@
 use dioxus::prelude::*;
-use rand::rngs::StdRng;
-use rand::{Rng, SeedableRng};
-
-#[derive(Clone, Debug)]
-struct Fish {
-    id: usize,
-    age: u32,
-    alive: bool,
-}
 
-impl Fish {
-    fn new(id: usize) -> Self {
-        Fish {
-            id,
-            age: 0,
-            alive: true,
-        }
-    }
+use crate::simulation::{DEFAULT_MAX_AGE, FishSimulation, SimConfig};
+use crate::topography::TopographicMap;
 
-    fn step(&mut self, rng: &mut StdRng, death_rate: f64) {
-        self.age += 1;
-        if rng.random_bool(death_rate) || self.age > 10 {
-            self.alive = false;
-        }
-    }
-}
+const MAP_WIDTH: usize = 96;
+const MAP_HEIGHT: usize = 64;
+
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 200.0;
+
+const RECENT_DEATHS: usize = 10;
+
+// Ticks kept for the chart and exports, so long autoplay runs stay bounded
+const HISTORY_LIMIT: usize = 1000;
 
-#[derive(Debug, Clone)]
-struct FishSimulation {
-    fish: Vec<Fish>,
-    next_id: usize,
-    rng: StdRng,
-    death_rate: f64,
-    spawn_threshold: usize,
-    spawn_count: usize,
-    history: Vec<usize>,
+/// SVG path through the population history, tick along x and population
+/// along y. ticks and max_population span the full width and height.
+fn history_path(
+    history: &[usize],
+    ticks: usize,
+    max_population: usize,
+    width: f64,
+    height: f64,
+) -> String {
+    let max_population = max_population.max(1) as f64;
+    let tick_width = if ticks > 1 {
+        width / (ticks - 1) as f64
+    } else {
+        0.0
+    };
+
+    history
+        .iter()
+        .enumerate()
+        .map(|(tick, &population)| {
+            let command = if tick == 0 { "M" } else { "L" };
+            let x = tick as f64 * tick_width;
+            let y = height - population as f64 / max_population * height;
+            format!("{}{:.1},{:.1}", command, x, y)
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
 }
 
-impl FishSimulation {
-    fn new_with_seed(
-        initial_count: usize,
-        death_rate: f64,
-        spawn_threshold: usize,
-        spawn_count: usize,
-        seed: u64,
-    ) -> Self {
-        let rng = StdRng::seed_from_u64(seed);
-        let fish = (0..initial_count).map(Fish::new).collect();
-        FishSimulation {
-            fish,
-            next_id: initial_count,
-            rng,
-            death_rate,
-            spawn_threshold,
-            spawn_count,
-            history: vec![initial_count],
-        }
-    }
+/// SVG paths for two runs on shared axes, so the larger population of either
+/// run touches the top edge and the longer run spans the full width.
+fn comparison_paths(a: &[usize], b: &[usize], width: f64, height: f64) -> (String, String) {
+    let ticks = a.len().max(b.len());
+    let max_population = a.iter().chain(b).copied().max().unwrap_or(0);
 
-    fn step(&mut self) {
-        for fish in &mut self.fish {
-            if fish.alive {
-                fish.step(&mut self.rng, self.death_rate);
-            }
-        }
+    (
+        history_path(a, ticks, max_population, width, height),
+        history_path(b, ticks, max_population, width, height),
+    )
+}
 
-        let alive_count = self.fish.iter().filter(|f| f.alive).count();
-        if alive_count < self.spawn_threshold {
-            self.spawn_fish(self.spawn_count);
+/// Percent-encoded data URL, usable as a download link's href.
+fn data_url(mime: &str, body: &str) -> String {
+    let mut url = format!("data:{};charset=utf-8,", mime);
+    for byte in body.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            url.push(byte as char);
+        } else {
+            url.push_str(&format!("%{:02X}", byte));
         }
-        self.history.push(self.population_count());
     }
 
-    fn spawn_fish(&mut self, count: usize) {
-        for _ in 0..count {
-            self.fish.push(Fish::new(self.next_id));
-            self.next_id += 1;
-        }
-    }
+    url
+}
 
-    fn alive_fish(&self) -> Vec<&Fish> {
-        self.fish.iter().filter(|f| f.alive).collect()
-    }
+/// Saves body as file_name through a throwaway download link. Called from
+/// click handlers, so exports are only encoded when someone asks for one.
+fn download(file_name: &str, mime: &str, body: &str) {
+    download_url(file_name, &data_url(mime, body));
+}
 
-    fn population_count(&self) -> usize {
-        self.alive_fish().len()
-    }
+// url must come from data_url, which percent-encodes everything but
+// unreserved characters, so it can't break out of the string literal
+fn download_url(file_name: &str, url: &str) {
+    let _ = document::eval(&format!(
+        "const link = document.createElement('a'); link.href = '{}'; link.download = '{}'; link.click();",
+        url,
+        file_name,
+    ));
+}
 
-    fn history(&self) -> &[usize] {
-        &self.history
-    }
+/// Data URL of a simulation's full state, restorable with
+/// FishSimulation::load_json.
+#[cfg(feature = "serde")]
+fn snapshot_url(sim: &FishSimulation) -> serde_json::Result<String> {
+    Ok(data_url("application/json", &sim.save_json()?))
 }
 
 #[component]
-fn App() -> Element {
+pub fn App() -> Element {
     let mut seed = use_signal(|| 42u64);
-    let mut sim = use_signal(|| FishSimulation::new_with_seed(20, 0.1, 10, 5, *seed.read()));
     let mut tick = use_signal(|| 0u64);
     let mut autoplay = use_signal(|| false);
+    let mut show_dead = use_signal(|| false);
+    let mut speed_ms = use_signal(|| 500u32);
+    let mut initial_count = use_signal(|| 20usize);
+    let mut max_age = use_signal(|| DEFAULT_MAX_AGE);
     let mut death_rate = use_signal(|| 0.1f64);
     let mut spawn_threshold = use_signal(|| 10usize);
     let mut spawn_count = use_signal(|| 5usize);
+    let mut death_rate_b = use_signal(|| 0.2f64);
+    let mut spawn_threshold_b = use_signal(|| 10usize);
+    let mut spawn_count_b = use_signal(|| 5usize);
+
+    let new_simulation = move |death_rate: Signal<f64>,
+                               spawn_threshold: Signal<usize>,
+                               spawn_count: Signal<usize>| {
+        let mut simulation = FishSimulation::from_config(SimConfig {
+            initial_count: *initial_count.read(),
+            death_rate: *death_rate.read(),
+            spawn_threshold: *spawn_threshold.read(),
+            spawn_count: *spawn_count.read(),
+            seed: *seed.read(),
+            max_age: *max_age.read(),
+        });
+        simulation.history_limit = Some(HISTORY_LIMIT);
+        simulation
+    };
+    let mut sim = use_signal(move || new_simulation(death_rate, spawn_threshold, spawn_count));
+    let mut sim_b =
+        use_signal(move || new_simulation(death_rate_b, spawn_threshold_b, spawn_count_b));
+
+    // Both runs advance together so their histories stay aligned
+    let mut step_both = move || {
+        sim.write()
+            .step_with_params(*death_rate.read(), *spawn_threshold.read(), *spawn_count.read());
+        sim_b.write().step_with_params(
+            *death_rate_b.read(),
+            *spawn_threshold_b.read(),
+            *spawn_count_b.read(),
+        );
+
+        tick += 1;
+    };
+    let mut reset_both = move || {
+        sim.set(new_simulation(death_rate, spawn_threshold, spawn_count));
+        sim_b.set(new_simulation(death_rate_b, spawn_threshold_b, spawn_count_b));
+        tick.set(0);
+    };
+    let mut map_scale = use_signal(|| 0.12f64);
+    let mut map = use_signal(|| {
+        TopographicMap::new(*seed.read() as u32, MAP_WIDTH, MAP_HEIGHT, *map_scale.read())
+    });
 
     use_future(move || async move {
         loop {
             if *autoplay.read() {
-                let simulation = &mut sim.write();
-                simulation.death_rate = *death_rate.read();
-                simulation.spawn_threshold = *spawn_threshold.read();
-                simulation.spawn_count = *spawn_count.read();
-                simulation.step();
+                step_both();
 
-                tick += 1;
+                // Nothing left to watch once both worlds are dead
+                if sim.read().is_extinct() && sim_b.read().is_extinct() {
+                    autoplay.set(false);
+                }
             }
 
-            gloo_timers::future::TimeoutFuture::new(500).await;
+            let delay = *speed_ms.read();
+            gloo_timers::future::TimeoutFuture::new(delay).await;
         }
     });
 
-    let chart_data = serde_json::to_string(sim.read().history()).unwrap();
+    let (chart_path, chart_path_b) = comparison_paths(
+        sim.read().history(),
+        sim_b.read().history(),
+        CHART_WIDTH,
+        CHART_HEIGHT,
+    );
+
+    let export_name = move || format!("fish_sim_seed{}_tick{}", seed.read(), tick.read());
+    let export_csv = move |_| {
+        let mut csv = Vec::new();
+        sim.read()
+            .history_to_csv(&mut csv)
+            .expect("Writing to a Vec cannot fail");
+        download(
+            &format!("{}.csv", export_name()),
+            "text/csv",
+            &String::from_utf8_lossy(&csv),
+        );
+    };
+    let export_json = move |_| {
+        let json = serde_json::json!({
+            "seed": *seed.read(),
+            "initial_count": *initial_count.read(),
+            "death_rate": *death_rate.read(),
+            "spawn_threshold": *spawn_threshold.read(),
+            "spawn_count": *spawn_count.read(),
+            "max_age": *max_age.read(),
+            "history": sim.read().history(),
+        });
+        download(
+            &format!("{}.json", export_name()),
+            "application/json",
+            &json.to_string(),
+        );
+    };
+
+    #[cfg(feature = "serde")]
+    let export_snapshot = move |run: Signal<FishSimulation>, label: &str| {
+        if let Ok(url) = snapshot_url(&run.read()) {
+            download_url(&format!("{}_snapshot_{}.json", export_name(), label), &url);
+        }
+    };
+    #[cfg(not(feature = "serde"))]
+    let export_snapshot = |_: Signal<FishSimulation>, _: &str| {};
 
     rsx! {
         div { class: "p-4 space-y-4",
@@ -127,16 +216,19 @@ fn App() -> Element {
             div { class: "flex gap-4 flex-wrap",
                 button {
                     class: "bg-blue-500 text-white px-4 py-2 rounded",
+                    onclick: move |_| step_both(),
+                    "Step"
+                }
+                button {
+                    class: "bg-gray-500 text-white px-4 py-2 rounded",
+                    disabled: !sim.read().can_step_back() || !sim_b.read().can_step_back(),
                     onclick: move |_| {
-                        let simulation = &mut sim.write();
-                        simulation.death_rate = *death_rate.read();
-                        simulation.spawn_threshold = *spawn_threshold.read();
-                        simulation.spawn_count = *spawn_count.read();
-                        simulation.step();
-
-                        tick += 1;
+                        if sim.write().step_back() {
+                            sim_b.write().step_back();
+                            tick -= 1;
+                        }
                     },
-                    "Step"
+                    "Step Back"
                 }
                 button {
                     class: "bg-green-500 text-white px-4 py-2 rounded",
@@ -145,10 +237,7 @@ fn App() -> Element {
                 }
                 button {
                     class: "bg-red-500 text-white px-4 py-2 rounded",
-                    onclick: move |_| {
-                        sim.set(FishSimulation::new_with_seed(20, *death_rate.read(), *spawn_threshold.read(), *spawn_count.read(), *seed.read()));
-                        tick.set(0);
-                    },
+                    onclick: move |_| reset_both(),
                     "Reset Simulation"
                 }
             }
@@ -166,13 +255,66 @@ fn App() -> Element {
                 }
                 button {
                     class: "bg-yellow-500 text-white px-4 py-2 rounded",
+                    onclick: move |_| reset_both(),
+                    "Apply New Seed"
+                }
+
+                label { "Autoplay Speed: {speed_ms.read()} ms/tick" }
+                input {
+                    r#type: "range",
+                    min: "50", max: "2000", step: "50",
+                    value: "{speed_ms.read()}",
+                    oninput: move |e| {
+                        if let Ok(val) = e.value().parse::<u32>() {
+                            speed_ms.set(val);
+                        }
+                    }
+                }
+
+                label { "Map Scale: {map_scale.read():.2}" }
+                input {
+                    r#type: "range",
+                    min: "0.02", max: "0.5", step: "0.01",
+                    value: "{map_scale.read()}",
+                    oninput: move |e| {
+                        if let Ok(val) = e.value().parse::<f64>() {
+                            map_scale.set(val);
+                        }
+                    }
+                }
+                button {
+                    class: "bg-teal-500 text-white px-4 py-2 rounded",
                     onclick: move |_| {
-                        sim.set(FishSimulation::new_with_seed(20, *death_rate.read(), *spawn_threshold.read(), *spawn_count.read(), *seed.read()));
-                        tick.set(0);
+                        if let Ok(new_map) = TopographicMap::try_new(*seed.read() as u32, MAP_WIDTH, MAP_HEIGHT, *map_scale.read()) {
+                            map.set(new_map);
+                        }
                     },
-                    "Apply New Seed"
+                    "Regenerate Map"
                 }
 
+                label { "Initial Count: {initial_count.read()}" }
+                input {
+                    r#type: "number",
+                    min: "0", max: "500",
+                    value: "{initial_count.read()}",
+                    oninput: move |e| {
+                        if let Ok(val) = e.value().parse::<usize>() {
+                            initial_count.set(val);
+                        }
+                    }
+                }
+                label { "Max Age: {max_age.read()}" }
+                input {
+                    r#type: "range",
+                    min: "1", max: "30", step: "1",
+                    value: "{max_age.read()}",
+                    oninput: move |e| {
+                        if let Ok(val) = e.value().parse::<u32>() {
+                            max_age.set(val);
+                        }
+                    }
+                }
+                h2 { class: "text-lg font-bold text-sky-700", "Run A" }
                 label { "Death Rate: {death_rate.read():.2}" }
                 input {
                     r#type: "range",
@@ -206,12 +348,109 @@ fn App() -> Element {
                         }
                     }
                 }
+
+                h2 { class: "text-lg font-bold text-orange-600", "Run B" }
+                label { "Death Rate: {death_rate_b.read():.2}" }
+                input {
+                    r#type: "range",
+                    min: "0.01", max: "0.9", step: "0.01",
+                    value: death_rate_b.read().to_string().as_str(),
+                    oninput: move |e| {
+                        if let Ok(val) = e.value().parse::<f64>() {
+                            death_rate_b.set(val);
+                        }
+                    }
+                }
+                label { "Spawn Threshold: {spawn_threshold_b.read()}" }
+                input {
+                    r#type: "range",
+                    min: "1", max: "50", step: "1",
+                    value: spawn_threshold_b.read().to_string().as_str(),
+                    oninput: move |e| {
+                        if let Ok(val) = e.value().parse::<usize>() {
+                            spawn_threshold_b.set(val);
+                        }
+                    }
+                }
+                label { "Spawn Count: {spawn_count_b.read()}" }
+                input {
+                    r#type: "range",
+                    min: "1", max: "20", step: "1",
+                    value: "{spawn_count_b.read()}",
+                    oninput: move |e| {
+                        if let Ok(val) = e.value().parse::<usize>() {
+                            spawn_count_b.set(val);
+                        }
+                    }
+                }
             }
+            pre { class: "font-mono text-xs leading-none", "{map.read().to_plain_string()}" }
             p { "Tick: {tick}" }
-            p { "Population: {sim.read().population_count()}" }
-            iframe {
+            p { "Population A: {sim.read().population_count()}" }
+            if let Some(extinct_tick) = sim.read().extinct_at() {
+                p { class: "text-red-600 font-bold", "Run A extinct at tick {extinct_tick}" }
+            }
+            label {
+                input {
+                    r#type: "checkbox",
+                    checked: *show_dead.read(),
+                    onchange: move |_| show_dead.toggle(),
+                }
+                " Show dead fish"
+            }
+            if *show_dead.read() {
+                p { "Dead A: {sim.read().dead_count()}" }
+                p {
+                    "Recently dead A: "
+                    for id in sim.read().recent_deaths(RECENT_DEATHS) {
+                        span { class: "mr-2", "#{id}" }
+                    }
+                }
+            }
+            p { "Population B: {sim_b.read().population_count()}" }
+            if let Some(extinct_tick) = sim_b.read().extinct_at() {
+                p { class: "text-red-600 font-bold", "Run B extinct at tick {extinct_tick}" }
+            }
+            div { class: "flex gap-4",
+                button {
+                    class: "bg-indigo-500 text-white px-4 py-2 rounded",
+                    onclick: export_csv,
+                    "Download CSV"
+                }
+                button {
+                    class: "bg-indigo-500 text-white px-4 py-2 rounded",
+                    onclick: export_json,
+                    "Download JSON"
+                }
+                if cfg!(feature = "serde") {
+                    button {
+                        class: "bg-purple-500 text-white px-4 py-2 rounded",
+                        onclick: move |_| export_snapshot(sim, "a"),
+                        "Export Snapshot A"
+                    }
+                    button {
+                        class: "bg-purple-500 text-white px-4 py-2 rounded",
+                        onclick: move |_| export_snapshot(sim_b, "b"),
+                        "Export Snapshot B"
+                    }
+                }
+            }
+            svg {
                 class: "w-full h-64 border",
-                srcdoc: (format!("<html><body><pre>{}</pre></body></html>", chart_data)).as_str()
+                view_box: "0 0 {CHART_WIDTH} {CHART_HEIGHT}",
+                preserve_aspect_ratio: "none",
+                path {
+                    d: "{chart_path}",
+                    fill: "none",
+                    stroke: "steelblue",
+                    stroke_width: "2",
+                }
+                path {
+                    d: "{chart_path_b}",
+                    fill: "none",
+                    stroke: "darkorange",
+                    stroke_width: "2",
+                }
             }
             ul {
                 for fish in sim.read().alive_fish().iter() {
@@ -221,3 +460,84 @@ fn App() -> Element {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_text_has_one_line_per_row() {
+        let map = TopographicMap::new(42, MAP_WIDTH, MAP_HEIGHT, 0.12);
+        let text = map.to_plain_string();
+
+        assert_eq!(text.lines().count(), MAP_HEIGHT);
+        assert!(text.lines().all(|line| line.chars().count() == MAP_WIDTH));
+    }
+
+    #[test]
+    fn history_path_scales_ticks_and_population() {
+        let path = history_path(&[0, 5, 10], 3, 10, 100.0, 50.0);
+
+        assert_eq!(path, "M0.0,50.0 L50.0,25.0 L100.0,0.0");
+    }
+
+    #[test]
+    fn history_path_of_single_tick_stays_on_left_edge() {
+        assert_eq!(history_path(&[4], 1, 4, 100.0, 50.0), "M0.0,0.0");
+        assert_eq!(history_path(&[], 0, 0, 100.0, 50.0), "");
+    }
+
+    #[test]
+    fn data_url_percent_encodes_all_but_unreserved_characters() {
+        assert_eq!(
+            data_url("text/csv", "tick,population\n0,20\n"),
+            "data:text/csv;charset=utf-8,tick%2Cpopulation%0A0%2C20%0A"
+        );
+        assert_eq!(
+            data_url("application/json", "{\"a-b_c.d~\": \"é'\"}"),
+            "data:application/json;charset=utf-8,%7B%22a-b_c.d~%22%3A%20%22%C3%A9%27%22%7D"
+        );
+    }
+
+    #[test]
+    fn comparison_paths_share_both_axes() {
+        let (a, b) = comparison_paths(&[0, 10], &[5, 5, 20], 100.0, 50.0);
+
+        // b's peak sets the top edge and its length the width for both runs
+        assert_eq!(a, "M0.0,50.0 L50.0,25.0");
+        assert_eq!(b, "M0.0,37.5 L50.0,37.5 L100.0,0.0");
+        assert_eq!(comparison_paths(&[], &[], 100.0, 50.0), (String::new(), String::new()));
+    }
+
+    // Inverse of data_url's percent-encoding
+    #[cfg(feature = "serde")]
+    fn percent_decode(encoded: &str) -> String {
+        let mut bytes = Vec::new();
+        let mut rest = encoded.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            if byte == b'%' {
+                let hex = std::str::from_utf8(&tail[..2]).unwrap();
+                bytes.push(u8::from_str_radix(hex, 16).unwrap());
+                rest = &tail[2..];
+            } else {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn snapshot_url_decodes_into_an_equal_simulation() {
+        let mut sim = FishSimulation::new_with_seed(20, 0.1, 5, 5, 42, DEFAULT_MAX_AGE);
+        sim.step_n(10);
+
+        let url = snapshot_url(&sim).unwrap();
+        let encoded = url.strip_prefix("data:application/json;charset=utf-8,").unwrap();
+
+        let loaded = FishSimulation::load_json(&percent_decode(encoded)).unwrap();
+        assert_eq!(loaded, sim);
+    }
+}
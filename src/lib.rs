@@ -0,0 +1,3 @@
+pub mod dioxus_app;
+pub mod simulation;
+pub mod topography;
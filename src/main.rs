@@ -1,7 +1,25 @@
-mod dioxus_app;
-mod topography;
+use fish_pop_sim::topography::{TopographicMap, TopographicRegion};
 
-use topography::TopographicMap;
+const USAGE: &str = "usage: fish_sim [x y]";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    PrintMap,
+    DescribeCell(usize, usize),
+}
+
+// Arguments after the program name. None prints the map, "x y" describes that cell.
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    match args {
+        [] => Ok(Command::PrintMap),
+        [x, y] => {
+            let x = x.parse().map_err(|_| format!("Invalid x coordinate: {}", x))?;
+            let y = y.parse().map_err(|_| format!("Invalid y coordinate: {}", y))?;
+            Ok(Command::DescribeCell(x, y))
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
 
 fn main() {
     //dioxus::launch(App);
@@ -11,6 +29,53 @@ fn main() {
     const HEIGHT: usize = 64;
     const SCALE: f64 = 0.12;
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match parse_args(&args) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
     let map = TopographicMap::new(SEED, WIDTH, HEIGHT, SCALE);
-    println!("{}", map);
+    match command {
+        Command::PrintMap => println!("{}", map),
+        Command::DescribeCell(x, y) => match map.get(x, y) {
+            Some(TopographicRegion::Land(land)) => {
+                println!("({}, {}): land, elevation {:.2}", x, y, land.elevation())
+            }
+            Some(TopographicRegion::Water(water)) => {
+                println!("({}, {}): {}", x, y, water.describe_in(map.depth_ranges()))
+            }
+            None => {
+                eprintln!("({}, {}) is outside the {}x{} map", x, y, WIDTH, HEIGHT);
+                std::process::exit(2);
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_args_dispatches_on_argument_count() {
+        assert_eq!(parse_args(&args(&[])), Ok(Command::PrintMap));
+        assert_eq!(parse_args(&args(&["3", "4"])), Ok(Command::DescribeCell(3, 4)));
+        assert_eq!(
+            parse_args(&args(&["x", "y"])),
+            Err("Invalid x coordinate: x".to_string())
+        );
+        assert_eq!(
+            parse_args(&args(&["3", "y"])),
+            Err("Invalid y coordinate: y".to_string())
+        );
+        assert_eq!(parse_args(&args(&["3"])), Err(USAGE.to_string()));
+    }
 }
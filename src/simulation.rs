@@ -0,0 +1,2533 @@
+use crate::topography::{DepthRangeName, TopographicMap, Vegetation};
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{self, Write};
+
+pub const DEFAULT_MAX_AGE: u32 = 10;
+
+// von Bertalanffy age offset so newborn fish have a small nonzero length
+const GROWTH_AGE_OFFSET: f64 = 0.5;
+
+// Fish school with same-species fish up to this many cells away
+const SCHOOLING_RADIUS: f64 = 3.0;
+
+// Fish won't move into water this many feet off their preferred depth
+// unless nothing better is adjacent
+const MAX_DEPTH_MISMATCH: f64 = 6.0;
+
+// Standard deviation of the change in growth gene from parents to offspring
+const MUTATION_SD: f64 = 0.05;
+
+// Growth genes never mutate below this, so every fish still grows
+const MIN_GROWTH_GENE: f64 = 0.1;
+
+// Infected fish pass disease to fish up to this many cells away
+const INFECTION_RADIUS: f64 = 1.5;
+
+// Added to an infected fish's death probability each step
+const DISEASE_DEATH_RATE: f64 = 0.2;
+
+// Chance each step that an infected fish which survived recovers for good
+const RECOVERY_RATE: f64 = 0.25;
+
+// Vegetation density a fish eats from its cell each step
+const GRAZING_RATE: f32 = 0.02;
+
+// Vegetation density every patch regrows each step
+const REGROWTH_RATE: f32 = 0.01;
+
+// Predators die after this many steps in a row without eating
+const STARVATION_STEPS: u32 = 3;
+
+// How many steps step_back can undo
+const MAX_SNAPSHOTS: usize = 100;
+
+// Fish a fully vegetated cell supports on top of its depth-based capacity
+const VEGETATION_CAPACITY: f64 = 4.0;
+
+// Spawning favors cover: a vegetated water cell is this many times as likely
+// to be picked as a bare one
+const VEGETATED_SPAWN_WEIGHT: f64 = 4.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MortalityModel {
+    /// Every fish dies with the same per-step probability.
+    #[default]
+    Flat,
+    /// Death probability rises linearly from the base rate at birth to
+    /// certainty at the maximum age.
+    AgeRamp,
+}
+
+impl MortalityModel {
+    pub fn death_probability(&self, death_rate: f64, age: u32, max_age: u32) -> f64 {
+        let death_rate = clamp_probability(death_rate);
+        match self {
+            MortalityModel::Flat => death_rate,
+            MortalityModel::AgeRamp => {
+                let age_fraction = (age as f64 / max_age.max(1) as f64).min(1.0);
+                death_rate + (1.0 - death_rate) * age_fraction
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GrowthModel {
+    /// Tops the population up by spawn_count whenever it drops below
+    /// spawn_threshold.
+    #[default]
+    ThresholdRefill,
+    /// Living fish at or above breeding_age pair up within their species and
+    /// each pair produces fecundity offspring per step on average.
+    Reproduction { breeding_age: u32, fecundity: f64 },
+    /// Net recruitment of r * N * (1 - N / k) per step, removing fish when the
+    /// population is above k. Natural mortality still applies on top, so use a
+    /// zero death rate for textbook logistic growth.
+    Logistic { r: f64, k: f64 },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Sex {
+    #[default]
+    Female,
+    Male,
+}
+
+impl Sex {
+    // Even odds of either sex
+    fn random<R: RngCore>(rng: &mut R) -> Self {
+        if rng.random_bool(0.5) {
+            Sex::Male
+        } else {
+            Sex::Female
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Species {
+    Bass,
+    #[default]
+    Bluegill,
+    Crappie,
+}
+
+impl Species {
+    pub const ALL: [Species; 3] = [Species::Bass, Species::Bluegill, Species::Crappie];
+
+    pub fn max_age(&self) -> u32 {
+        match self {
+            Species::Bass => 16,
+            Species::Bluegill => 8,
+            Species::Crappie => 10,
+        }
+    }
+
+    pub fn death_rate(&self) -> f64 {
+        match self {
+            Species::Bass => 0.05,
+            Species::Bluegill => 0.15,
+            Species::Crappie => 0.1,
+        }
+    }
+
+    pub fn spawn_count(&self) -> usize {
+        match self {
+            Species::Bass => 2,
+            Species::Bluegill => 6,
+            Species::Crappie => 4,
+        }
+    }
+
+    /// Depth in feet this species holds at.
+    pub fn preferred_depth(&self) -> f64 {
+        match self {
+            Species::Bass => 6.0,
+            Species::Bluegill => 3.0,
+            Species::Crappie => 9.0,
+        }
+    }
+
+    /// Asymptotic length and growth coefficient of the von Bertalanffy curve.
+    fn growth_curve(&self) -> (f64, f64) {
+        match self {
+            Species::Bass => (60.0, 0.2),
+            Species::Bluegill => (25.0, 0.3),
+            Species::Crappie => (35.0, 0.25),
+        }
+    }
+
+    /// a and b in W = a * L^b, with L in cm and W in kg.
+    fn length_weight(&self) -> (f64, f64) {
+        match self {
+            Species::Bass => (1.2e-5, 3.05),
+            Species::Bluegill => (1.5e-5, 3.1),
+            Species::Crappie => (1.0e-5, 3.1),
+        }
+    }
+
+    pub fn length_at_age(&self, age: u32) -> f64 {
+        let (max_length, k) = self.growth_curve();
+        max_length * (1.0 - (-k * (age as f64 + GROWTH_AGE_OFFSET)).exp())
+    }
+
+    pub fn weight_at_length(&self, length_cm: f64) -> f64 {
+        let (a, b) = self.length_weight();
+        a * length_cm.powf(b)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SimEvent {
+    Death {
+        id: usize,
+    },
+    Spawn {
+        id: usize,
+    },
+    /// A fish arrived from outside the simulated water.
+    Immigration {
+        id: usize,
+    },
+    /// A fish was released into the water by stocking.
+    Stocked {
+        id: usize,
+    },
+    /// A prey fish was eaten by a predator.
+    Eaten {
+        id: usize,
+        predator: usize,
+    },
+    /// The last living fish died this tick.
+    Extinction,
+    /// The population outgrew the map's carrying capacity.
+    CapacityExceeded,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fish {
+    pub id: usize,
+    pub age: u32,
+    pub alive: bool,
+    pub species: Species,
+    pub sex: Sex,
+    /// Map cell the fish occupies. Always (0, 0) in simulations without a map.
+    pub x: usize,
+    pub y: usize,
+    pub length_cm: f64,
+    /// Scales the species' maximum length. 1.0 grows exactly like the
+    /// species curve.
+    pub growth_gene: f64,
+    /// Tick the fish was spawned in. The initial population is born at 0.
+    pub born_at: u64,
+    /// Steps in a row a predator has gone without eating.
+    pub hunger: u32,
+    pub infected: bool,
+    /// Recovered from infection and can't catch it again.
+    pub immune: bool,
+}
+
+impl Fish {
+    pub fn new(id: usize, species: Species) -> Self {
+        Fish {
+            id,
+            age: 0,
+            alive: true,
+            species,
+            sex: Sex::default(),
+            x: 0,
+            y: 0,
+            length_cm: species.length_at_age(0),
+            growth_gene: 1.0,
+            born_at: 0,
+            hunger: 0,
+            infected: false,
+            immune: false,
+        }
+    }
+
+    pub fn weight_kg(&self) -> f64 {
+        self.species.weight_at_length(self.length_cm)
+    }
+
+    /// Can still catch the disease.
+    pub fn is_susceptible(&self) -> bool {
+        self.alive && !self.infected && !self.immune
+    }
+
+    pub fn step<R: RngCore>(
+        &mut self,
+        rng: &mut R,
+        mortality: MortalityModel,
+        death_rate: f64,
+        max_age: u32,
+    ) {
+        self.age += 1;
+        self.length_cm = self.species.length_at_age(self.age) * self.growth_gene;
+        let death_probability = mortality.death_probability(death_rate, self.age, max_age);
+        if rng.random_bool(death_probability) || self.age > max_age {
+            self.alive = false;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SimConfig {
+    pub initial_count: usize,
+    pub death_rate: f64,
+    pub spawn_threshold: usize,
+    pub spawn_count: usize,
+    pub seed: u64,
+    pub max_age: u32,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            initial_count: 20,
+            death_rate: 0.1,
+            spawn_threshold: 10,
+            spawn_count: 5,
+            seed: 42,
+            max_age: DEFAULT_MAX_AGE,
+        }
+    }
+}
+
+// State needed to undo a step. Parameters are not part of it, but the map's
+// vegetation is since fish graze it. Fish holds only the living ones, as dead
+// fish are pruned after every step.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    fish: Vec<Fish>,
+    next_id: usize,
+    dead: usize,
+    rng: ChaCha8Rng,
+    events_len: usize,
+    vegetation: Option<Vec<f32>>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FishSimulation {
+    /// Fish alive at the start of the step. Dead ones are pruned once it ends.
+    fish: Vec<Fish>,
+    next_id: usize,
+    /// Fish that have died or been caught, and so were pruned from fish.
+    dead: usize,
+    rng: ChaCha8Rng,
+    death_rate: f64,
+    pub spawn_threshold: usize,
+    pub spawn_count: usize,
+    pub max_age: u32,
+    pub mortality: MortalityModel,
+    pub growth: GrowthModel,
+    /// Species present in the simulation. Spawned fish cycle through them.
+    species: Vec<Species>,
+    /// When set, each fish uses its species' max age, death rate and spawn
+    /// count instead of the simulation-wide values.
+    pub species_parameters: bool,
+    /// Per-step probability that each eligible living fish is caught.
+    harvest_rate: f64,
+    /// Only fish at least this heavy are harvested. 0.0 harvests every fish.
+    pub harvest_min_kg: f64,
+    /// How strongly fish on a map steer toward nearby fish of their species.
+    /// 0.0 is a pure random walk.
+    pub cohesion: f64,
+    /// Per-step chance an infected fish passes the disease to each fish in
+    /// contact with it.
+    transmission_rate: f64,
+    /// Mean number of fish arriving from outside each step, regardless of
+    /// the current population.
+    immigration_rate: f64,
+    /// Keeps only this many of the most recent ticks in each history and in
+    /// the event log. None keeps them all.
+    pub history_limit: Option<usize>,
+    /// How many steps step_back can undo. 0 skips snapshotting entirely.
+    #[cfg_attr(feature = "serde", serde(default = "default_undo_limit"))]
+    pub undo_limit: usize,
+    /// Species that eats every other species. None disables predation.
+    predator: Option<Species>,
+    /// Tick of the first entry still in the histories.
+    history_start: usize,
+    history: Vec<usize>,
+    age_history: Vec<f64>,
+    caught_history: Vec<usize>,
+    infected_history: Vec<usize>,
+    predator_history: Vec<usize>,
+    prey_history: Vec<usize>,
+    /// Everything that happened, tagged with the tick it happened in.
+    events: Vec<(u64, SimEvent)>,
+    map: Option<TopographicMap>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    snapshots: VecDeque<Snapshot>,
+    /// Indices into fish by map cell, rebuilt after every step. May still
+    /// list fish that died since.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    cell_index: BTreeMap<(usize, usize), Vec<usize>>,
+}
+
+// Undo snapshots and the cell index aren't saved with the rest of the state,
+// so they don't count toward equality
+impl PartialEq for FishSimulation {
+    fn eq(&self, other: &Self) -> bool {
+        let Self {
+            fish,
+            next_id,
+            dead,
+            rng,
+            death_rate,
+            spawn_threshold,
+            spawn_count,
+            max_age,
+            mortality,
+            growth,
+            species,
+            species_parameters,
+            harvest_rate,
+            harvest_min_kg,
+            cohesion,
+            transmission_rate,
+            immigration_rate,
+            history_limit,
+            undo_limit,
+            predator,
+            history_start,
+            history,
+            age_history,
+            caught_history,
+            infected_history,
+            predator_history,
+            prey_history,
+            events,
+            map,
+            snapshots: _,
+            cell_index: _,
+        } = self;
+
+        *fish == other.fish
+            && *next_id == other.next_id
+            && *dead == other.dead
+            && *rng == other.rng
+            && *death_rate == other.death_rate
+            && *spawn_threshold == other.spawn_threshold
+            && *spawn_count == other.spawn_count
+            && *max_age == other.max_age
+            && *mortality == other.mortality
+            && *growth == other.growth
+            && *species == other.species
+            && *species_parameters == other.species_parameters
+            && *harvest_rate == other.harvest_rate
+            && *harvest_min_kg == other.harvest_min_kg
+            && *cohesion == other.cohesion
+            && *transmission_rate == other.transmission_rate
+            && *immigration_rate == other.immigration_rate
+            && *history_limit == other.history_limit
+            && *undo_limit == other.undo_limit
+            && *predator == other.predator
+            && *history_start == other.history_start
+            && *history == other.history
+            && *age_history == other.age_history
+            && *caught_history == other.caught_history
+            && *infected_history == other.infected_history
+            && *predator_history == other.predator_history
+            && *prey_history == other.prey_history
+            && *events == other.events
+            && *map == other.map
+    }
+}
+
+impl FishSimulation {
+    pub fn new_with_seed(
+        initial_count: usize,
+        death_rate: f64,
+        spawn_threshold: usize,
+        spawn_count: usize,
+        seed: u64,
+        max_age: u32,
+    ) -> Self {
+        Self::from_config(SimConfig {
+            initial_count,
+            death_rate,
+            spawn_threshold,
+            spawn_count,
+            seed,
+            max_age,
+        })
+    }
+
+    /// death_rate is clamped to [0.0, 1.0].
+    pub fn from_config(config: SimConfig) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
+        let fish = (0..config.initial_count)
+            .map(|id| Fish {
+                sex: Sex::random(&mut rng),
+                ..Fish::new(id, Species::default())
+            })
+            .collect();
+        FishSimulation {
+            fish,
+            next_id: config.initial_count,
+            dead: 0,
+            rng,
+            death_rate: clamp_probability(config.death_rate),
+            spawn_threshold: config.spawn_threshold,
+            spawn_count: config.spawn_count,
+            max_age: config.max_age,
+            mortality: MortalityModel::default(),
+            growth: GrowthModel::default(),
+            species: vec![Species::default()],
+            species_parameters: false,
+            harvest_rate: 0.0,
+            harvest_min_kg: 0.0,
+            cohesion: 0.0,
+            transmission_rate: 0.0,
+            immigration_rate: 0.0,
+            history_limit: None,
+            undo_limit: MAX_SNAPSHOTS,
+            predator: None,
+            history_start: 0,
+            history: vec![config.initial_count],
+            age_history: vec![0.0],
+            caught_history: vec![0],
+            infected_history: vec![0],
+            predator_history: vec![0],
+            prey_history: vec![config.initial_count],
+            events: Vec::new(),
+            map: None,
+            snapshots: VecDeque::new(),
+            cell_index: BTreeMap::new(),
+        }
+        .with_cell_index()
+    }
+
+    fn with_cell_index(mut self) -> Self {
+        self.rebuild_cell_index();
+        self
+    }
+
+    fn rebuild_cell_index(&mut self) {
+        self.cell_index.clear();
+        for (index, fish) in self.fish.iter().enumerate() {
+            if fish.alive {
+                self.cell_index
+                    .entry((fish.x, fish.y))
+                    .or_default()
+                    .push(index);
+            }
+        }
+    }
+
+    /// Like new_with_seed, but fish live on the map's water cells and wander
+    /// between them each step.
+    pub fn new_on_map(
+        map: TopographicMap,
+        initial_count: usize,
+        death_rate: f64,
+        spawn_threshold: usize,
+        spawn_count: usize,
+        seed: u64,
+        max_age: u32,
+    ) -> Self {
+        let mut simulation =
+            Self::new_with_seed(0, death_rate, spawn_threshold, spawn_count, seed, max_age);
+        simulation.map = Some(map);
+
+        simulation.spawn_fish(initial_count);
+        simulation.history = vec![simulation.population_count()];
+        simulation.prey_history = vec![simulation.population_count()];
+        simulation.events.clear();
+        for fish in &mut simulation.fish {
+            fish.born_at = 0;
+        }
+
+        simulation
+    }
+
+    /// A mixed population of initial_count fish per species, each following
+    /// its own species' parameters.
+    pub fn new_with_species(
+        species: &[Species],
+        initial_count: usize,
+        spawn_threshold: usize,
+        seed: u64,
+    ) -> Self {
+        let species = if species.is_empty() {
+            vec![Species::default()]
+        } else {
+            species.to_vec()
+        };
+        let first = species[0];
+
+        let mut simulation = Self::new_with_seed(
+            0,
+            first.death_rate(),
+            spawn_threshold,
+            first.spawn_count(),
+            seed,
+            first.max_age(),
+        );
+        simulation.species = species;
+        simulation.species_parameters = true;
+
+        for kind in simulation.species.clone() {
+            simulation.spawn_species(kind, initial_count);
+        }
+        simulation.history = vec![simulation.population_count()];
+        simulation.prey_history = vec![simulation.population_count()];
+        simulation.events.clear();
+        for fish in &mut simulation.fish {
+            fish.born_at = 0;
+        }
+
+        simulation
+    }
+
+    pub fn step(&mut self) {
+        let mut rng = self.rng.clone();
+        self.step_with_rng(&mut rng);
+        self.rng = rng;
+    }
+
+    /// Applies the tunable parameters, e.g. from UI controls, then steps.
+    pub fn step_with_params(
+        &mut self,
+        death_rate: f64,
+        spawn_threshold: usize,
+        spawn_count: usize,
+    ) {
+        self.set_death_rate(death_rate);
+        self.spawn_threshold = spawn_threshold;
+        self.spawn_count = spawn_count;
+        self.step();
+    }
+
+    /// Steps using the given RNG in place of the simulation's own seeded one,
+    /// which is left untouched. Useful for scripting outcomes in tests.
+    pub fn step_with_rng<R: RngCore>(&mut self, rng: &mut R) {
+        while self.snapshots.len() >= self.undo_limit.max(1) {
+            self.snapshots.pop_front();
+        }
+        if self.undo_limit > 0 {
+            self.snapshots.push_back(Snapshot {
+                fish: self.fish.clone(),
+                next_id: self.next_id,
+                dead: self.dead,
+                rng: self.rng.clone(),
+                events_len: self.events.len(),
+                vegetation: self.map.as_ref().map(TopographicMap::vegetation_densities),
+            });
+        }
+
+        let tick = self.next_tick();
+
+        for fish in &mut self.fish {
+            if fish.alive {
+                let (death_rate, max_age) = if self.species_parameters {
+                    (fish.species.death_rate(), fish.species.max_age())
+                } else {
+                    (self.death_rate, self.max_age)
+                };
+                fish.step(rng, self.mortality, death_rate, max_age);
+                if !fish.alive {
+                    self.events.push((tick, SimEvent::Death { id: fish.id }));
+                }
+            }
+        }
+
+        let mut caught = 0;
+        if self.harvest_rate > 0.0 {
+            for fish in self.fish.iter_mut().filter(|f| f.alive) {
+                if fish.weight_kg() >= self.harvest_min_kg && rng.random_bool(self.harvest_rate) {
+                    fish.alive = false;
+                    caught += 1;
+                    self.events.push((tick, SimEvent::Death { id: fish.id }));
+                }
+            }
+        }
+        self.caught_history.push(caught);
+
+        self.spread_disease(rng, tick);
+
+        if let Some(map) = &self.map {
+            // Crowding past the habitat's capacity kills the excess on average
+            let capacity = self.carrying_capacity(map);
+            let population = self.population_count();
+            if population > capacity {
+                self.events.push((tick, SimEvent::CapacityExceeded));
+                let crowding_death_rate = 1.0 - capacity as f64 / population as f64;
+                for fish in self.fish.iter_mut().filter(|f| f.alive) {
+                    if rng.random_bool(crowding_death_rate) {
+                        fish.alive = false;
+                        self.events.push((tick, SimEvent::Death { id: fish.id }));
+                    }
+                }
+            }
+
+            // Schools are located from positions at the start of the move
+            let school_centers: Vec<Option<(f64, f64)>> = self
+                .fish
+                .iter()
+                .map(|fish| {
+                    if !fish.alive || self.cohesion <= 0.0 {
+                        return None;
+                    }
+
+                    let school: Vec<&Fish> = self
+                        .fish_within(fish.x, fish.y, SCHOOLING_RADIUS)
+                        .into_iter()
+                        .filter(|other| other.species == fish.species && other.id != fish.id)
+                        .collect();
+                    if school.is_empty() {
+                        return None;
+                    }
+
+                    let count = school.len() as f64;
+                    Some((
+                        school.iter().map(|other| other.x as f64).sum::<f64>() / count,
+                        school.iter().map(|other| other.y as f64).sum::<f64>() / count,
+                    ))
+                })
+                .collect();
+
+            let cohesion = self.cohesion.max(0.0);
+            for (fish, center) in self.fish.iter_mut().zip(school_centers) {
+                if !fish.alive {
+                    continue;
+                }
+
+                let (x, y) = (fish.x, fish.y);
+                let preferred_depth = fish.species.preferred_depth();
+                (fish.x, fish.y) = random_walk(map, rng, x, y, |nx, ny| {
+                    let schooling = center.map_or(1.0, |(cx, cy)| {
+                        let before = (x as f64 - cx).hypot(y as f64 - cy);
+                        let after = (nx as f64 - cx).hypot(ny as f64 - cy);
+                        1.0 + cohesion * (before - after).max(0.0)
+                    });
+                    schooling * depth_weight(map, nx, ny, preferred_depth)
+                });
+            }
+        }
+
+        // Fish eat down the vegetation where they end up, then it all grows back a little
+        if let Some(map) = &mut self.map {
+            for fish in self.fish.iter().filter(|f| f.alive) {
+                map.graze(fish.x, fish.y, GRAZING_RATE);
+            }
+            map.regrow_vegetation(REGROWTH_RATE);
+        }
+
+        self.predation(rng, tick);
+
+        match self.growth {
+            GrowthModel::ThresholdRefill => self.refill(rng),
+            GrowthModel::Reproduction {
+                breeding_age,
+                fecundity,
+            } => self.reproduce(rng, breeding_age, fecundity),
+            GrowthModel::Logistic { r, k } => self.logistic(rng, r, k),
+        }
+        self.immigrate(rng, tick);
+
+        let previous = self.history.last().copied().unwrap_or(0);
+        let population = self.population_count();
+        if previous > 0 && population == 0 {
+            self.events.push((tick, SimEvent::Extinction));
+        }
+
+        self.history.push(population);
+        self.age_history.push(self.mean_age());
+        self.infected_history.push(self.infected_count());
+        self.predator_history.push(self.predator_count());
+        self.prey_history.push(self.prey_count());
+        self.trim_history();
+
+        let count = self.fish.len();
+        self.fish.retain(|f| f.alive);
+        self.dead += count - self.fish.len();
+        self.rebuild_cell_index();
+    }
+
+    /// Undoes the most recent step, restoring the fish, RNG and map. Returns
+    /// false when there is nothing left to undo.
+    pub fn step_back(&mut self) -> bool {
+        if !self.can_step_back() {
+            return false;
+        }
+        let Some(snapshot) = self.snapshots.pop_back() else {
+            return false;
+        };
+
+        self.fish = snapshot.fish;
+        self.next_id = snapshot.next_id;
+        self.dead = snapshot.dead;
+        self.rng = snapshot.rng;
+        self.events.truncate(snapshot.events_len);
+        if let (Some(map), Some(vegetation)) = (&mut self.map, snapshot.vegetation) {
+            map.set_vegetation_densities(&vegetation);
+        }
+        self.history.pop();
+        self.age_history.pop();
+        self.caught_history.pop();
+        self.infected_history.pop();
+        self.predator_history.pop();
+        self.prey_history.pop();
+        self.rebuild_cell_index();
+
+        true
+    }
+
+    /// False once the history has been trimmed back to the current tick.
+    pub fn can_step_back(&self) -> bool {
+        !self.snapshots.is_empty() && self.history.len() > 1
+    }
+
+    // Drops the oldest entries and their events past history_limit, keeping
+    // at least the current tick
+    fn trim_history(&mut self) {
+        let Some(limit) = self.history_limit else {
+            return;
+        };
+
+        let excess = self.history.len().saturating_sub(limit.max(1));
+        if excess == 0 {
+            return;
+        }
+
+        self.history.drain(..excess);
+        self.age_history.drain(..excess.min(self.age_history.len()));
+        self.caught_history
+            .drain(..excess.min(self.caught_history.len()));
+        self.infected_history
+            .drain(..excess.min(self.infected_history.len()));
+        self.predator_history
+            .drain(..excess.min(self.predator_history.len()));
+        self.prey_history
+            .drain(..excess.min(self.prey_history.len()));
+        self.history_start += excess;
+
+        // Events are in tick order. Snapshots count events from the front, so
+        // they shift down by as many as are dropped.
+        let stale = self
+            .events
+            .partition_point(|(tick, _)| (*tick as usize) < self.history_start);
+        self.events.drain(..stale);
+        for snapshot in &mut self.snapshots {
+            snapshot.events_len = snapshot.events_len.saturating_sub(stale);
+        }
+    }
+
+    // Infected fish die, recover or stay sick, then pass the disease on to
+    // nearby fish, or to random ones when there's no map. Nothing is drawn
+    // from the rng while nobody is infected.
+    fn spread_disease<R: RngCore>(&mut self, rng: &mut R, tick: u64) {
+        if !self.fish.iter().any(|f| f.alive && f.infected) {
+            return;
+        }
+
+        for fish in self.fish.iter_mut().filter(|f| f.alive && f.infected) {
+            if rng.random_bool(DISEASE_DEATH_RATE) {
+                fish.alive = false;
+                self.events.push((tick, SimEvent::Death { id: fish.id }));
+            } else if rng.random_bool(RECOVERY_RATE) {
+                fish.infected = false;
+                fish.immune = true;
+            }
+        }
+
+        if self.transmission_rate <= 0.0 {
+            return;
+        }
+
+        let infected: Vec<usize> = (0..self.fish.len())
+            .filter(|&index| self.fish[index].alive && self.fish[index].infected)
+            .collect();
+        let living: Vec<usize> = (0..self.fish.len())
+            .filter(|&index| self.fish[index].alive)
+            .collect();
+
+        // Every contact is found before anyone new gets sick
+        let mut contacts = Vec::new();
+        for &index in &infected {
+            if self.map.is_some() {
+                let fish = &self.fish[index];
+                contacts.extend(
+                    self.fish_within(fish.x, fish.y, INFECTION_RADIUS)
+                        .into_iter()
+                        .filter(|other| other.id != fish.id)
+                        .map(|other| other.id),
+                );
+            } else {
+                let contact = &self.fish[living[rng.random_range(0..living.len())]];
+                if contact.id != self.fish[index].id {
+                    contacts.push(contact.id);
+                }
+            }
+        }
+
+        for id in contacts {
+            let Some(fish) = self.fish.iter_mut().find(|f| f.id == id) else {
+                continue;
+            };
+            if fish.is_susceptible() && rng.random_bool(self.transmission_rate) {
+                fish.infected = true;
+            }
+        }
+    }
+
+    // Each predator eats one random prey fish in its own or an adjacent cell.
+    // Predators that go too long without a meal starve.
+    fn predation<R: RngCore>(&mut self, rng: &mut R, tick: u64) {
+        let Some(predator_species) = self.predator else {
+            return;
+        };
+
+        // Fish have moved since the last step
+        self.rebuild_cell_index();
+
+        for index in 0..self.fish.len() {
+            let predator = &self.fish[index];
+            if !predator.alive || predator.species != predator_species {
+                continue;
+            }
+
+            let (x, y) = (predator.x, predator.y);
+            let prey: Vec<usize> = (x.saturating_sub(1)..=x + 1)
+                .flat_map(|nx| (y.saturating_sub(1)..=y + 1).map(move |ny| (nx, ny)))
+                .filter_map(|cell| self.cell_index.get(&cell))
+                .flatten()
+                .copied()
+                .filter(|&other| {
+                    self.fish[other].alive && self.fish[other].species != predator_species
+                })
+                .collect();
+
+            if prey.is_empty() {
+                let predator = &mut self.fish[index];
+                predator.hunger += 1;
+                if predator.hunger > STARVATION_STEPS {
+                    predator.alive = false;
+                    self.events
+                        .push((tick, SimEvent::Death { id: predator.id }));
+                }
+                continue;
+            }
+
+            let eaten = prey[rng.random_range(0..prey.len())];
+            self.fish[eaten].alive = false;
+            self.fish[index].hunger = 0;
+            self.events.push((
+                tick,
+                SimEvent::Eaten {
+                    id: self.fish[eaten].id,
+                    predator: self.fish[index].id,
+                },
+            ));
+        }
+    }
+
+    // Arrivals are Poisson distributed around immigration_rate
+    fn immigrate<R: RngCore>(&mut self, rng: &mut R, tick: u64) {
+        if self.immigration_rate <= 0.0 {
+            return;
+        }
+
+        let limit = (-self.immigration_rate).exp();
+        let mut product: f64 = rng.random();
+        let mut arrivals = 0;
+        while product > limit {
+            arrivals += 1;
+            product *= rng.random::<f64>();
+        }
+
+        for _ in 0..arrivals {
+            let kind = self.species[self.next_id % self.species.len()];
+            let id = self.place_fish(rng, kind);
+            self.events.push((tick, SimEvent::Immigration { id }));
+        }
+    }
+
+    fn refill<R: RngCore>(&mut self, rng: &mut R) {
+        let alive_count = self.fish.iter().filter(|f| f.alive).count();
+        if alive_count < self.spawn_threshold {
+            if self.species_parameters {
+                for kind in self.species.clone() {
+                    self.spawn_species_with_rng(rng, kind, kind.spawn_count());
+                }
+            } else {
+                self.spawn_fish_with_rng(rng, self.spawn_count);
+            }
+        }
+    }
+
+    fn reproduce<R: RngCore>(&mut self, rng: &mut R, breeding_age: u32, fecundity: f64) {
+        for kind in self.species.clone() {
+            let breeders = |sex: Sex| -> Vec<f64> {
+                self.fish
+                    .iter()
+                    .filter(|f| {
+                        f.alive && f.species == kind && f.sex == sex && f.age >= breeding_age
+                    })
+                    .map(|f| f.growth_gene)
+                    .collect()
+            };
+            let mothers = breeders(Sex::Female);
+            let fathers = breeders(Sex::Male);
+
+            // Broods are limited by the scarcer sex. The fractional part of
+            // the expected brood is a seeded roll.
+            let pairs = mothers.len().min(fathers.len());
+            let expected = pairs as f64 * fecundity.max(0.0);
+            let mut offspring = expected.floor() as usize;
+            if rng.random_bool(expected.fract()) {
+                offspring += 1;
+            }
+
+            // Each offspring gets the mean gene of a random mother and father, mutated
+            for _ in 0..offspring {
+                let mother = mothers[rng.random_range(0..mothers.len())];
+                let father = fathers[rng.random_range(0..fathers.len())];
+                let gene = (mother + father) / 2.0 + gaussian(rng) * MUTATION_SD;
+
+                let id = self.place_fish(rng, kind);
+                let fish = self.fish.last_mut().expect("Indexed element must exist");
+                fish.growth_gene = gene.max(MIN_GROWTH_GENE);
+                fish.length_cm *= fish.growth_gene;
+                self.events.push((self.next_tick(), SimEvent::Spawn { id }));
+            }
+        }
+    }
+
+    fn logistic<R: RngCore>(&mut self, rng: &mut R, r: f64, k: f64) {
+        if k <= 0.0 {
+            return;
+        }
+
+        // The fractional part of the net recruitment is a seeded roll
+        let population = self.population_count() as f64;
+        let recruitment = r * population * (1.0 - population / k);
+        let mut change = recruitment.abs().floor() as usize;
+        if rng.random_bool(clamp_probability(recruitment.abs().fract())) {
+            change += 1;
+        }
+
+        if recruitment >= 0.0 {
+            self.spawn_fish_with_rng(rng, change);
+            return;
+        }
+
+        let tick = self.next_tick();
+        let mut alive: Vec<usize> = (0..self.fish.len())
+            .filter(|&index| self.fish[index].alive)
+            .collect();
+        for _ in 0..change.min(alive.len()) {
+            let index = alive.swap_remove(rng.random_range(0..alive.len()));
+            let fish = &mut self.fish[index];
+            fish.alive = false;
+            self.events.push((tick, SimEvent::Death { id: fish.id }));
+        }
+    }
+
+    pub fn death_rate(&self) -> f64 {
+        self.death_rate
+    }
+
+    /// Clamped to [0.0, 1.0].
+    pub fn set_death_rate(&mut self, death_rate: f64) {
+        self.death_rate = clamp_probability(death_rate);
+    }
+
+    pub fn transmission_rate(&self) -> f64 {
+        self.transmission_rate
+    }
+
+    /// Clamped to [0.0, 1.0].
+    pub fn set_transmission_rate(&mut self, transmission_rate: f64) {
+        self.transmission_rate = clamp_probability(transmission_rate);
+    }
+
+    /// Infects up to count random susceptible fish and returns how many were
+    /// infected.
+    pub fn infect(&mut self, count: usize) -> usize {
+        let mut rng = self.rng.clone();
+        let mut susceptible: Vec<usize> = (0..self.fish.len())
+            .filter(|&index| self.fish[index].is_susceptible())
+            .collect();
+        let count = count.min(susceptible.len());
+        for _ in 0..count {
+            let index = susceptible.swap_remove(rng.random_range(0..susceptible.len()));
+            self.fish[index].infected = true;
+        }
+        self.rng = rng;
+
+        let infected = self.infected_count();
+        if let Some(last) = self.infected_history.last_mut() {
+            *last = infected;
+        }
+
+        count
+    }
+
+    pub fn infected_count(&self) -> usize {
+        self.fish.iter().filter(|f| f.alive && f.infected).count()
+    }
+
+    pub fn immigration_rate(&self) -> f64 {
+        self.immigration_rate
+    }
+
+    /// Negative and NaN rates disable immigration.
+    pub fn set_immigration_rate(&mut self, immigration_rate: f64) {
+        self.immigration_rate = if immigration_rate > 0.0 {
+            immigration_rate
+        } else {
+            0.0
+        };
+    }
+
+    pub fn predator(&self) -> Option<Species> {
+        self.predator
+    }
+
+    /// Makes the species prey on every other species, or turns predation off
+    /// with None.
+    pub fn set_predator(&mut self, predator: Option<Species>) {
+        self.predator = predator;
+
+        let (predators, prey) = (self.predator_count(), self.prey_count());
+        if let Some(last) = self.predator_history.last_mut() {
+            *last = predators;
+        }
+        if let Some(last) = self.prey_history.last_mut() {
+            *last = prey;
+        }
+    }
+
+    /// Living predators, 0 without a predator species.
+    pub fn predator_count(&self) -> usize {
+        self.fish
+            .iter()
+            .filter(|f| f.alive && Some(f.species) == self.predator)
+            .count()
+    }
+
+    /// Living fish that aren't predators.
+    pub fn prey_count(&self) -> usize {
+        self.fish
+            .iter()
+            .filter(|f| f.alive && Some(f.species) != self.predator)
+            .count()
+    }
+
+    pub fn harvest_rate(&self) -> f64 {
+        self.harvest_rate
+    }
+
+    /// Clamped to [0.0, 1.0].
+    pub fn set_harvest_rate(&mut self, harvest_rate: f64) {
+        self.harvest_rate = clamp_probability(harvest_rate);
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn save_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn load_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json).map(Self::with_cell_index)
+    }
+
+    /// One row per tick with the population, mean age and fish caught.
+    pub fn history_to_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "tick,population,mean_age,caught")?;
+
+        for (index, population) in self.history.iter().enumerate() {
+            let mean_age = self.age_history.get(index).copied().unwrap_or(0.0);
+            let caught = self.caught_history.get(index).copied().unwrap_or(0);
+            let tick = self.history_start + index;
+            writeln!(writer, "{},{},{},{}", tick, population, mean_age, caught)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn step_n(&mut self, n: usize) {
+        for _ in 0..n {
+            self.step();
+        }
+    }
+
+    /// Steps until the population first hits zero and returns that tick, or
+    /// None if it survives max_ticks more steps.
+    pub fn run_until_extinct(&mut self, max_ticks: usize) -> Option<usize> {
+        if self.population_count() == 0 {
+            return Some(self.tick());
+        }
+
+        for _ in 0..max_ticks {
+            self.step();
+            if self.population_count() == 0 {
+                return Some(self.tick());
+            }
+        }
+
+        None
+    }
+
+    pub fn spawn_fish(&mut self, count: usize) {
+        let mut rng = self.rng.clone();
+        self.spawn_fish_with_rng(&mut rng, count);
+        self.rng = rng;
+    }
+
+    pub fn spawn_species(&mut self, species: Species, count: usize) {
+        let mut rng = self.rng.clone();
+        self.spawn_species_with_rng(&mut rng, species, count);
+        self.rng = rng;
+    }
+
+    /// Releases count newborn fish of the species. They go on the given map
+    /// cell when it is water, otherwise they are spread over the water like
+    /// natural spawn.
+    pub fn stock(&mut self, count: usize, species: Species, at: Option<(usize, usize)>) {
+        let at = at.filter(|&(x, y)| {
+            self.map
+                .as_ref()
+                .is_some_and(|map| map.water_at(x, y).is_some())
+        });
+
+        let mut rng = self.rng.clone();
+        for _ in 0..count {
+            let id = self.place_fish_at(&mut rng, species, at);
+            self.events
+                .push((self.next_tick(), SimEvent::Stocked { id }));
+        }
+        self.rng = rng;
+    }
+
+    fn spawn_fish_with_rng<R: RngCore>(&mut self, rng: &mut R, count: usize) {
+        for _ in 0..count {
+            let kind = self.species[self.next_id % self.species.len()];
+            self.spawn_species_with_rng(rng, kind, 1);
+        }
+    }
+
+    fn spawn_species_with_rng<R: RngCore>(&mut self, rng: &mut R, species: Species, count: usize) {
+        for _ in 0..count {
+            let id = self.place_fish(rng, species);
+            self.events.push((self.next_tick(), SimEvent::Spawn { id }));
+        }
+    }
+
+    // Adds a newborn fish on a random water cell and returns its id
+    fn place_fish<R: RngCore>(&mut self, rng: &mut R, species: Species) -> usize {
+        self.place_fish_at(rng, species, None)
+    }
+
+    // Like place_fish, but on the given cell when there is one
+    fn place_fish_at<R: RngCore>(
+        &mut self,
+        rng: &mut R,
+        species: Species,
+        at: Option<(usize, usize)>,
+    ) -> usize {
+        let id = self.next_id;
+        let mut fish = Fish::new(id, species);
+        fish.sex = Sex::random(rng);
+        fish.born_at = self.next_tick();
+        if let Some((x, y)) = at.or_else(|| {
+            self.map
+                .as_ref()
+                .and_then(|map| random_water_cell(map, rng))
+        }) {
+            fish.x = x;
+            fish.y = y;
+        }
+
+        self.cell_index
+            .entry((fish.x, fish.y))
+            .or_default()
+            .push(self.fish.len());
+        self.fish.push(fish);
+        self.next_id += 1;
+
+        id
+    }
+
+    pub fn alive_fish(&self) -> Vec<&Fish> {
+        self.fish.iter().filter(|f| f.alive).collect()
+    }
+
+    /// The heaviest living fish.
+    pub fn biggest_fish(&self) -> Option<&Fish> {
+        self.fish
+            .iter()
+            .filter(|f| f.alive)
+            .max_by(|a, b| a.weight_kg().total_cmp(&b.weight_kg()))
+    }
+
+    pub fn trophy_count(&self, min_kg: f64) -> usize {
+        self.fish
+            .iter()
+            .filter(|f| f.alive && f.weight_kg() >= min_kg)
+            .count()
+    }
+
+    /// Living fish at the given map cell.
+    pub fn fish_at(&self, x: usize, y: usize) -> Vec<&Fish> {
+        self.cell_index
+            .get(&(x, y))
+            .into_iter()
+            .flatten()
+            .map(|&index| &self.fish[index])
+            .filter(|f| f.alive)
+            .collect()
+    }
+
+    /// Living fish within radius cells of (x, y) by straight-line distance.
+    /// Distances do not wrap around the map edges.
+    pub fn fish_within(&self, x: usize, y: usize, radius: f64) -> Vec<&Fish> {
+        if radius.is_nan() || radius < 0.0 {
+            return Vec::new();
+        }
+
+        let within = |cell_x: usize, cell_y: usize| {
+            (cell_x as f64 - x as f64).hypot(cell_y as f64 - y as f64) <= radius
+        };
+
+        // Scan the occupied cells instead when the search box is bigger
+        let reach = radius.floor() as usize;
+        let side = reach.saturating_mul(2).saturating_add(1);
+        let mut found = Vec::new();
+        if side.saturating_mul(side) > self.cell_index.len() {
+            for &(cell_x, cell_y) in self.cell_index.keys() {
+                if within(cell_x, cell_y) {
+                    found.extend(self.fish_at(cell_x, cell_y));
+                }
+            }
+        } else {
+            for cell_y in y.saturating_sub(reach)..=y.saturating_add(reach) {
+                for cell_x in x.saturating_sub(reach)..=x.saturating_add(reach) {
+                    if within(cell_x, cell_y) {
+                        found.extend(self.fish_at(cell_x, cell_y));
+                    }
+                }
+            }
+        }
+
+        found
+    }
+
+    /// How many fish the map's water can support. Shallow cells hold more
+    /// than deep ones and vegetation adds cover on top.
+    pub fn carrying_capacity(&self, map: &TopographicMap) -> usize {
+        let capacity: f64 = map
+            .cells()
+            .filter_map(|(x, y, _)| Some((map.water_at(x, y)?, map.range_name_at(x, y))))
+            .map(|(water, range_name)| {
+                let depth_capacity = match range_name {
+                    Some(DepthRangeName::SuperShallow) | Some(DepthRangeName::Shallow) => 2.0,
+                    Some(DepthRangeName::MidDepth) => 1.0,
+                    Some(DepthRangeName::Deep) | None => 0.5,
+                };
+                depth_capacity + VEGETATION_CAPACITY * water.vegetation_density() as f64
+            })
+            .sum();
+
+        capacity as usize
+    }
+
+    pub fn map(&self) -> Option<&TopographicMap> {
+        self.map.as_ref()
+    }
+
+    pub fn population_count(&self) -> usize {
+        self.alive_fish().len()
+    }
+
+    /// Every fish that has died or been caught since the simulation began.
+    pub fn dead_count(&self) -> usize {
+        self.dead + self.fish.len() - self.population_count()
+    }
+
+    /// Ids of up to count of the most recently dead fish, newest first.
+    pub fn recent_deaths(&self, count: usize) -> Vec<usize> {
+        self.events
+            .iter()
+            .rev()
+            .filter_map(|(_, event)| match event {
+                SimEvent::Death { id } | SimEvent::Eaten { id, .. } => Some(*id),
+                _ => None,
+            })
+            .take(count)
+            .collect()
+    }
+
+    pub fn species(&self) -> &[Species] {
+        &self.species
+    }
+
+    pub fn count_by_species(&self) -> HashMap<Species, usize> {
+        let mut counts = HashMap::new();
+        for fish in self.alive_fish() {
+            *counts.entry(fish.species).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    /// Population at the end of each tick, starting from history_start.
+    pub fn history(&self) -> &[usize] {
+        &self.history
+    }
+
+    /// Tick of the first history entry. Nonzero once history_limit has
+    /// dropped older ticks.
+    pub fn history_start(&self) -> usize {
+        self.history_start
+    }
+
+    pub fn age_history(&self) -> &[f64] {
+        &self.age_history
+    }
+
+    pub fn events(&self) -> &[(u64, SimEvent)] {
+        &self.events
+    }
+
+    /// Fish caught each tick, aligned with history.
+    pub fn caught_history(&self) -> &[usize] {
+        &self.caught_history
+    }
+
+    /// Living infected fish at the end of each tick, aligned with history.
+    pub fn infected_history(&self) -> &[usize] {
+        &self.infected_history
+    }
+
+    /// Living predators at the end of each tick, aligned with history.
+    pub fn predator_history(&self) -> &[usize] {
+        &self.predator_history
+    }
+
+    /// Living prey at the end of each tick, aligned with history.
+    pub fn prey_history(&self) -> &[usize] {
+        &self.prey_history
+    }
+
+    pub fn total_caught(&self) -> usize {
+        self.caught_history.iter().sum()
+    }
+
+    /// Living fish counted by age. Fish older than max_age (possible after
+    /// lowering it mid-run) are counted in the last bucket.
+    pub fn age_histogram(&self) -> Vec<usize> {
+        let max_age = self.max_age as usize;
+        let mut histogram = vec![0; max_age + 1];
+        for fish in self.alive_fish() {
+            histogram[(fish.age as usize).min(max_age)] += 1;
+        }
+
+        histogram
+    }
+
+    /// Living fish counted by the tick they were born in.
+    pub fn cohort_sizes(&self) -> BTreeMap<u64, usize> {
+        let mut cohorts = BTreeMap::new();
+        for fish in self.alive_fish() {
+            *cohorts.entry(fish.born_at).or_insert(0) += 1;
+        }
+
+        cohorts
+    }
+
+    /// Share of the living fish that are male, 0.0 with no fish.
+    pub fn sex_ratio(&self) -> f64 {
+        let alive = self.alive_fish();
+        if alive.is_empty() {
+            return 0.0;
+        }
+
+        alive.iter().filter(|fish| fish.sex == Sex::Male).count() as f64 / alive.len() as f64
+    }
+
+    /// Mean growth gene of the living fish.
+    pub fn mean_gene(&self) -> f64 {
+        let alive = self.alive_fish();
+        if alive.is_empty() {
+            return 0.0;
+        }
+
+        alive.iter().map(|fish| fish.growth_gene).sum::<f64>() / alive.len() as f64
+    }
+
+    pub fn mean_age(&self) -> f64 {
+        let alive = self.alive_fish();
+        if alive.is_empty() {
+            return 0.0;
+        }
+
+        alive.iter().map(|fish| fish.age as f64).sum::<f64>() / alive.len() as f64
+    }
+
+    pub fn tick(&self) -> usize {
+        self.history_start + self.history.len() - 1
+    }
+
+    // Tick that the next step, or anything happening before it, belongs to
+    fn next_tick(&self) -> u64 {
+        (self.tick() + 1) as u64
+    }
+
+    pub fn is_extinct(&self) -> bool {
+        self.population_count() == 0
+    }
+
+    /// The tick the population last hit zero, while it is still extinct.
+    pub fn extinct_at(&self) -> Option<usize> {
+        if !self.is_extinct() {
+            return None;
+        }
+
+        Some(
+            self.history
+                .iter()
+                .rposition(|&population| population > 0)
+                .map_or(self.history_start, |index| self.history_start + index + 1),
+        )
+    }
+}
+
+/// Aggregate outcome of a batch of runs. Means are NaN for an empty batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchStats {
+    pub runs: usize,
+    pub mean_final_population: f64,
+    pub min_final_population: usize,
+    pub max_final_population: usize,
+    /// Fraction of runs that went extinct within the tick budget.
+    pub extinction_fraction: f64,
+    /// Mean extinction tick over the runs that went extinct, NaN if none did.
+    pub mean_time_to_extinction: f64,
+}
+
+/// Runs a fresh simulation from config for each seed, ticks steps each.
+pub fn run_batch(
+    config: SimConfig,
+    seeds: impl IntoIterator<Item = u64>,
+    ticks: usize,
+) -> BatchStats {
+    let mut finals = Vec::new();
+    let mut extinction_ticks = Vec::new();
+
+    for seed in seeds {
+        let mut simulation = FishSimulation::from_config(SimConfig { seed, ..config });
+        // Batches never step back
+        simulation.undo_limit = 0;
+        if let Some(tick) = simulation.run_until_extinct(ticks) {
+            extinction_ticks.push(tick);
+        }
+        finals.push(simulation.population_count());
+    }
+
+    let runs = finals.len();
+    let mean = |values: &[usize]| values.iter().sum::<usize>() as f64 / values.len() as f64;
+    BatchStats {
+        runs,
+        mean_final_population: mean(&finals),
+        min_final_population: finals.iter().copied().min().unwrap_or(0),
+        max_final_population: finals.iter().copied().max().unwrap_or(0),
+        extinction_fraction: extinction_ticks.len() as f64 / runs as f64,
+        mean_time_to_extinction: mean(&extinction_ticks),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn default_undo_limit() -> usize {
+    MAX_SNAPSHOTS
+}
+
+// Probabilities fed to random_bool must lie in [0.0, 1.0]. NaN counts as 0.0.
+fn clamp_probability(probability: f64) -> f64 {
+    if probability.is_nan() {
+        0.0
+    } else {
+        probability.clamp(0.0, 1.0)
+    }
+}
+
+// Standard normal sample by the Box-Muller transform
+fn gaussian<R: RngCore>(rng: &mut R) -> f64 {
+    let u1: f64 = 1.0 - rng.random::<f64>();
+    let u2: f64 = rng.random();
+    (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
+
+fn random_water_cell<R: RngCore>(map: &TopographicMap, rng: &mut R) -> Option<(usize, usize)> {
+    let water: Vec<(usize, usize, f64)> = map
+        .cells()
+        .filter_map(|(x, y, _)| {
+            let water = map.water_at(x, y)?;
+            let vegetated = Vegetation::ALL
+                .iter()
+                .any(|vegetation| water.has_vegetation_type(vegetation));
+            Some((
+                x,
+                y,
+                if vegetated {
+                    VEGETATED_SPAWN_WEIGHT
+                } else {
+                    1.0
+                },
+            ))
+        })
+        .collect();
+    if water.is_empty() {
+        return None;
+    }
+
+    let total: f64 = water.iter().map(|&(_, _, weight)| weight).sum();
+    let mut roll = rng.random_range(0.0..total);
+    for &(x, y, weight) in &water {
+        if roll < weight {
+            return Some((x, y));
+        }
+        roll -= weight;
+    }
+
+    water.last().map(|&(x, y, _)| (x, y))
+}
+
+// Closer to the preferred depth is better, too far off is avoided entirely
+fn depth_weight(map: &TopographicMap, x: usize, y: usize, preferred_depth: f64) -> f64 {
+    let Some(water) = map.water_at(x, y) else {
+        return 0.0;
+    };
+
+    let mismatch = (water.depth().value() - preferred_depth).abs();
+    if mismatch > MAX_DEPTH_MISMATCH {
+        return 0.0;
+    }
+
+    1.0 / (1.0 + mismatch)
+}
+
+// One step to a random adjacent water cell, each picked in proportion to its
+// weight. Fish hemmed in by land stay put.
+fn random_walk<R: RngCore, F: Fn(usize, usize) -> f64>(
+    map: &TopographicMap,
+    rng: &mut R,
+    x: usize,
+    y: usize,
+    weight: F,
+) -> (usize, usize) {
+    let moves: Vec<((usize, usize), f64)> = map
+        .orthogonal_neighbors(x, y)
+        .filter(|&(nx, ny)| map.water_at(nx, ny).is_some())
+        .map(|(nx, ny)| ((nx, ny), weight(nx, ny).max(0.0)))
+        .collect();
+    if moves.is_empty() {
+        return (x, y);
+    }
+
+    // Equal weights keep the plain uniform draw
+    let total: f64 = moves.iter().map(|(_, weight)| weight).sum();
+    if total <= 0.0 || moves.iter().all(|(_, weight)| *weight == moves[0].1) {
+        return moves[rng.random_range(0..moves.len())].0;
+    }
+
+    let mut roll = rng.random_range(0.0..total);
+    for &(cell, weight) in &moves {
+        if roll < weight {
+            return cell;
+        }
+        roll -= weight;
+    }
+
+    moves[moves.len() - 1].0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Replays scripted outputs in order, repeating the last one once they run
+    // out. random_bool(p) with 0.0 < p < 1.0 is true for 0 and false for u64::MAX.
+    struct ScriptedRng {
+        values: Vec<u64>,
+        next: usize,
+    }
+
+    impl ScriptedRng {
+        fn new(values: Vec<u64>) -> Self {
+            ScriptedRng { values, next: 0 }
+        }
+    }
+
+    impl RngCore for ScriptedRng {
+        fn next_u32(&mut self) -> u32 {
+            (self.next_u64() >> 32) as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            let value = self.values[self.next.min(self.values.len() - 1)];
+            self.next += 1;
+            value
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for chunk in dest.chunks_mut(8) {
+                let bytes = self.next_u64().to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+    }
+
+    fn small_map() -> TopographicMap {
+        TopographicMap::new(42, 32, 24, 0.12)
+    }
+
+    fn ids(simulation: &FishSimulation) -> Vec<usize> {
+        simulation.alive_fish().iter().map(|fish| fish.id).collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_run() {
+        let mut a = FishSimulation::new_with_seed(20, 0.3, 10, 5, 7, DEFAULT_MAX_AGE);
+        let mut b = FishSimulation::new_with_seed(20, 0.3, 10, 5, 7, DEFAULT_MAX_AGE);
+        let mut c = FishSimulation::new_with_seed(20, 0.3, 10, 5, 8, DEFAULT_MAX_AGE);
+        a.step_n(50);
+        b.step_n(50);
+        c.step_n(50);
+
+        assert_eq!(a.history(), b.history());
+        assert_eq!(ids(&a), ids(&b));
+        assert_ne!(a.history(), c.history());
+    }
+
+    #[test]
+    fn fish_die_past_max_age() {
+        let mut simulation = FishSimulation::new_with_seed(20, 0.0, 0, 0, 42, 3);
+
+        simulation.step_n(3);
+        assert_eq!(simulation.population_count(), 20);
+        simulation.step();
+        assert_eq!(simulation.population_count(), 0);
+        assert_eq!(simulation.history(), &[20, 20, 20, 20, 0]);
+    }
+
+    #[test]
+    fn run_until_extinct_matches_manual_loop() {
+        let mut run = FishSimulation::new_with_seed(20, 0.3, 0, 0, 42, DEFAULT_MAX_AGE);
+        let mut manual = run.clone();
+
+        let extinct_at = run.run_until_extinct(100);
+        let mut ticks = 0;
+        while manual.population_count() > 0 {
+            manual.step();
+            ticks += 1;
+        }
+
+        assert_eq!(extinct_at, Some(ticks));
+        assert_eq!(run.history(), manual.history());
+        assert_eq!(run.run_until_extinct(10), Some(ticks));
+
+        let mut refilled = FishSimulation::new_with_seed(20, 0.3, 10, 5, 42, DEFAULT_MAX_AGE);
+        assert_eq!(refilled.run_until_extinct(50), None);
+        assert_eq!(refilled.tick(), 50);
+    }
+
+    #[test]
+    fn age_ramp_kills_old_fish_faster() {
+        let ramp = MortalityModel::AgeRamp;
+        let probabilities: Vec<f64> = (0..=10)
+            .map(|age| ramp.death_probability(0.1, age, 10))
+            .collect();
+        assert_eq!(probabilities[0], 0.1);
+        assert_eq!(probabilities[10], 1.0);
+        assert!(probabilities.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(MortalityModel::Flat.death_probability(0.1, 9, 10), 0.1);
+
+        let deaths_at_age = |age: u32| {
+            let mut simulation = FishSimulation::new_with_seed(1000, 0.1, 0, 0, 42, 10);
+            simulation.mortality = MortalityModel::AgeRamp;
+            for fish in &mut simulation.fish {
+                fish.age = age;
+            }
+            simulation.step();
+            1000 - simulation.population_count()
+        };
+        assert!(deaths_at_age(7) > deaths_at_age(0) * 3);
+    }
+
+    #[test]
+    fn age_histogram_tracks_ages() {
+        let mut simulation = FishSimulation::new_with_seed(20, 0.0, 0, 0, 42, 5);
+
+        assert_eq!(simulation.age_histogram(), vec![20, 0, 0, 0, 0, 0]);
+        simulation.step();
+        assert_eq!(simulation.age_histogram(), vec![0, 20, 0, 0, 0, 0]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn saved_run_resumes_identically() {
+        let mut straight = FishSimulation::new_with_seed(20, 0.2, 10, 5, 42, DEFAULT_MAX_AGE);
+        straight.step_n(10);
+
+        let mut saved = FishSimulation::new_with_seed(20, 0.2, 10, 5, 42, DEFAULT_MAX_AGE);
+        saved.step_n(5);
+        let mut resumed = FishSimulation::load_json(&saved.save_json().unwrap()).unwrap();
+        resumed.step_n(5);
+
+        assert_eq!(resumed.history(), straight.history());
+        assert_eq!(resumed.age_history(), straight.age_history());
+        assert_eq!(resumed.events(), straight.events());
+        assert_eq!(ids(&resumed), ids(&straight));
+    }
+
+    #[test]
+    fn species_counts_sum_to_population() {
+        let mut simulation = FishSimulation::new_with_species(&Species::ALL, 10, 5, 42);
+        assert_eq!(simulation.count_by_species()[&Species::Crappie], 10);
+
+        for _ in 0..20 {
+            simulation.step();
+            let counts = simulation.count_by_species();
+            assert_eq!(
+                counts.values().sum::<usize>(),
+                simulation.population_count()
+            );
+        }
+    }
+
+    #[test]
+    fn reproduction_grows_geometrically() {
+        let mut simulation = FishSimulation::new_with_seed(40, 0.0, 0, 0, 42, 100);
+        simulation.growth = GrowthModel::Reproduction {
+            breeding_age: 0,
+            fecundity: 1.0,
+        };
+        simulation.step_n(10);
+
+        // Roughly half the fish find a mate, so each step adds about half again
+        for pair in simulation.history().windows(2) {
+            let ratio = pair[1] as f64 / pair[0] as f64;
+            assert!((1.3..1.7).contains(&ratio), "{:?}", simulation.history());
+        }
+    }
+
+    #[test]
+    fn fish_on_a_map_stay_in_water() {
+        let mut simulation = FishSimulation::new_on_map(small_map(), 50, 0.1, 30, 10, 42, 10);
+
+        for _ in 0..50 {
+            let map = simulation.map().unwrap();
+            for fish in simulation.alive_fish() {
+                assert!(map.water_at(fish.x, fish.y).is_some(), "{:?}", fish);
+            }
+            simulation.step();
+        }
+    }
+
+    #[test]
+    fn spawning_favors_vegetated_cells() {
+        let map = small_map();
+        let vegetated = |x: usize, y: usize| {
+            map.water_at(x, y)
+                .is_some_and(|water| !water.vegetation().is_empty())
+        };
+        let water_cells = map.cells().filter(|(_, _, r)| !r.is_land()).count() as f64;
+        let vegetated_cells = map.cells().filter(|&(x, y, _)| vegetated(x, y)).count() as f64;
+        let expected = vegetated_cells * VEGETATED_SPAWN_WEIGHT
+            / (vegetated_cells * VEGETATED_SPAWN_WEIGHT + water_cells - vegetated_cells);
+
+        let mut rng = ChaCha8Rng::seed_from_u64(42);
+        let draws = 10_000;
+        let hits = (0..draws)
+            .filter(|_| {
+                let (x, y) = random_water_cell(&map, &mut rng).unwrap();
+                vegetated(x, y)
+            })
+            .count();
+        let share = hits as f64 / draws as f64;
+
+        assert!(expected > vegetated_cells / water_cells);
+        assert!((share - expected).abs() < 0.02, "{} vs {}", share, expected);
+    }
+
+    #[test]
+    fn vegetation_raises_carrying_capacity() {
+        let weedy = small_map();
+        let mut barren = weedy.clone();
+        for (x, y) in weedy.cells().map(|(x, y, _)| (x, y)) {
+            barren.graze(x, y, 1.0);
+        }
+        let simulation = FishSimulation::new_with_seed(0, 0.1, 0, 0, 42, 10);
+
+        let depth_only: f64 = barren
+            .cells()
+            .filter_map(|(x, y, _)| barren.water_at(x, y).map(|_| barren.range_name_at(x, y)))
+            .map(|range_name| match range_name {
+                Some(DepthRangeName::SuperShallow) | Some(DepthRangeName::Shallow) => 2.0,
+                Some(DepthRangeName::MidDepth) => 1.0,
+                _ => 0.5,
+            })
+            .sum();
+        assert_eq!(simulation.carrying_capacity(&barren), depth_only as usize);
+        assert!(simulation.carrying_capacity(&weedy) > simulation.carrying_capacity(&barren));
+    }
+
+    #[test]
+    fn fish_get_heavier_with_age() {
+        for species in Species::ALL {
+            let weights: Vec<f64> = (0..=species.max_age())
+                .map(|age| species.weight_at_length(species.length_at_age(age)))
+                .collect();
+            assert!(
+                weights.windows(2).all(|pair| pair[0] < pair[1]),
+                "{:?}",
+                species
+            );
+        }
+
+        let mut simulation = FishSimulation::new_with_seed(5, 0.0, 0, 0, 42, 10);
+        simulation.fish[3].age = 4;
+        simulation.step();
+        assert_eq!(simulation.biggest_fish().map(|fish| fish.id), Some(3));
+        assert_eq!(simulation.trophy_count(simulation.fish[3].weight_kg()), 1);
+    }
+
+    #[test]
+    fn harvest_settles_below_carrying_capacity() {
+        let run = |harvest_rate: f64| {
+            let mut simulation = FishSimulation::new_with_seed(100, 0.0, 0, 0, 42, 1000);
+            simulation.growth = GrowthModel::Logistic { r: 0.5, k: 200.0 };
+            simulation.set_harvest_rate(harvest_rate);
+            simulation.step_n(200);
+            simulation
+        };
+        let tail_mean = |simulation: &FishSimulation| {
+            let tail = &simulation.history()[150..];
+            tail.iter().sum::<usize>() as f64 / tail.len() as f64
+        };
+
+        let unharvested = run(0.0);
+        let harvested = run(0.2);
+
+        // Logistic growth with proportional harvest h settles near k * (1 - h / r)
+        assert!((tail_mean(&unharvested) - 200.0).abs() < 10.0);
+        assert!((tail_mean(&harvested) - 120.0).abs() < 20.0);
+        assert_eq!(unharvested.total_caught(), 0);
+        assert_eq!(
+            harvested.total_caught(),
+            harvested.caught_history().iter().sum::<usize>()
+        );
+        assert!(harvested.total_caught() > 0);
+    }
+
+    #[test]
+    fn history_csv_has_a_row_per_tick() {
+        let mut simulation = FishSimulation::new_with_seed(20, 0.2, 10, 5, 42, 10);
+        simulation.step_n(12);
+
+        let mut csv = Vec::new();
+        simulation.history_to_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines[0], "tick,population,mean_age,caught");
+        assert_eq!(lines.len(), 1 + 13);
+        assert_eq!(lines[1], "0,20,0,0");
+        let last: Vec<&str> = lines[13].split(',').collect();
+        assert_eq!(last[0], "12");
+        assert_eq!(last[1], simulation.population_count().to_string());
+    }
+
+    #[test]
+    fn scripted_rng_kills_exactly_the_first_fish() {
+        let mut simulation = FishSimulation::new_with_seed(5, 0.5, 0, 0, 42, 10);
+        let seeded = simulation.rng.clone();
+        let mut rng = ScriptedRng::new(vec![0, u64::MAX]);
+
+        simulation.step_with_rng(&mut rng);
+
+        assert_eq!(ids(&simulation), vec![1, 2, 3, 4]);
+        assert_eq!(simulation.events(), &[(1, SimEvent::Death { id: 0 })]);
+        assert_eq!(rng.next, 5);
+        assert_eq!(simulation.rng, seeded);
+    }
+
+    #[test]
+    fn death_events_account_for_every_loss() {
+        let mut simulation = FishSimulation::new_with_seed(30, 0.3, 0, 0, 42, 10);
+        let extinct_at = simulation.run_until_extinct(100).unwrap() as u64;
+
+        for (tick, pair) in simulation.history().windows(2).enumerate() {
+            let tick = tick as u64 + 1;
+            let deaths = simulation
+                .events()
+                .iter()
+                .filter(|(at, event)| *at == tick && matches!(event, SimEvent::Death { .. }))
+                .count();
+            assert_eq!(deaths, pair[0] - pair[1], "tick {}", tick);
+        }
+
+        let extinctions: Vec<u64> = simulation
+            .events()
+            .iter()
+            .filter(|(_, event)| *event == SimEvent::Extinction)
+            .map(|(tick, _)| *tick)
+            .collect();
+        assert_eq!(extinctions, vec![extinct_at]);
+    }
+
+    #[test]
+    fn config_defaults_and_clamping() {
+        let config = SimConfig::default();
+        assert_eq!(
+            config,
+            SimConfig {
+                initial_count: 20,
+                death_rate: 0.1,
+                spawn_threshold: 10,
+                spawn_count: 5,
+                seed: 42,
+                max_age: DEFAULT_MAX_AGE,
+            }
+        );
+
+        let simulation = FishSimulation::from_config(config);
+        assert_eq!(simulation.population_count(), 20);
+        assert_eq!(simulation.death_rate(), 0.1);
+
+        for (death_rate, clamped) in [(-0.5, 0.0), (1.5, 1.0), (f64::NAN, 0.0)] {
+            let simulation = FishSimulation::from_config(SimConfig {
+                death_rate,
+                ..config
+            });
+            assert_eq!(simulation.death_rate(), clamped);
+        }
+    }
+
+    #[test]
+    fn out_of_range_death_rate_acts_as_certain_death() {
+        let mut over = FishSimulation::new_with_seed(20, 1.5, 0, 0, 42, 10);
+        let mut certain = FishSimulation::new_with_seed(20, 1.0, 0, 0, 42, 10);
+        over.step_n(3);
+        certain.step_n(3);
+
+        assert_eq!(over.history(), &[20, 0, 0, 0]);
+        assert_eq!(over.history(), certain.history());
+
+        over.set_death_rate(7.0);
+        assert_eq!(over.death_rate(), 1.0);
+    }
+
+    #[test]
+    fn batch_mean_lies_between_min_and_max() {
+        let config = SimConfig {
+            death_rate: 0.3,
+            ..SimConfig::default()
+        };
+        let stats = run_batch(config, 0..20, 30);
+
+        assert_eq!(stats.runs, 20);
+        assert!(stats.min_final_population < stats.max_final_population);
+        assert!(stats.min_final_population as f64 <= stats.mean_final_population);
+        assert!(stats.mean_final_population <= stats.max_final_population as f64);
+        assert!((0.0..=1.0).contains(&stats.extinction_fraction));
+
+        let empty = run_batch(config, std::iter::empty(), 30);
+        assert_eq!(empty.runs, 0);
+        assert!(empty.mean_final_population.is_nan());
+    }
+
+    #[test]
+    fn logistic_growth_approaches_k() {
+        for initial_count in [10, 300] {
+            let mut simulation = FishSimulation::new_with_seed(initial_count, 0.0, 0, 0, 42, 1000);
+            simulation.growth = GrowthModel::Logistic { r: 0.5, k: 150.0 };
+            simulation.step_n(60);
+
+            let population = simulation.population_count() as f64;
+            assert!((population - 150.0).abs() <= 3.0, "{}", population);
+        }
+    }
+
+    #[test]
+    fn step_with_params_applies_params_before_stepping() {
+        let mut tuned = FishSimulation::from_config(SimConfig::default());
+        let mut manual = tuned.clone();
+
+        tuned.step_with_params(0.4, 3, 7);
+        manual.set_death_rate(0.4);
+        manual.spawn_threshold = 3;
+        manual.spawn_count = 7;
+        manual.step();
+
+        assert_eq!(tuned.death_rate(), 0.4);
+        assert_eq!(tuned.spawn_threshold, 3);
+        assert_eq!(tuned.spawn_count, 7);
+        assert_eq!(tuned.history(), manual.history());
+        assert_eq!(ids(&tuned), ids(&manual));
+    }
+
+    #[test]
+    fn config_params_flow_into_the_simulation() {
+        let mut simulation = FishSimulation::from_config(SimConfig {
+            initial_count: 37,
+            death_rate: 0.0,
+            spawn_count: 0,
+            max_age: 3,
+            ..SimConfig::default()
+        });
+
+        assert_eq!(simulation.population_count(), 37);
+        assert_eq!(simulation.max_age, 3);
+
+        simulation.step_n(3);
+        assert_eq!(simulation.population_count(), 37);
+        simulation.step();
+        assert_eq!(simulation.population_count(), 0);
+    }
+
+    #[test]
+    fn step_back_restores_the_previous_state() {
+        let mut simulation = FishSimulation::new_on_map(small_map(), 40, 0.3, 10, 5, 7, 8);
+        simulation.step_n(3);
+        let before = simulation.clone();
+
+        simulation.step();
+        assert!(simulation.dead_count() > before.dead_count());
+        assert!(simulation.fish.iter().all(|f| f.alive));
+        assert!(simulation.step_back());
+
+        assert_eq!(simulation.population_count(), before.population_count());
+        assert_eq!(simulation.dead_count(), before.dead_count());
+        assert_eq!(ids(&simulation), ids(&before));
+        assert_eq!(simulation.rng, before.rng);
+        assert_eq!(simulation.history(), before.history());
+        assert_eq!(simulation.events, before.events);
+        assert_eq!(simulation.map, before.map);
+
+        // Replaying from the restored state takes the same path again
+        let mut replay = before.clone();
+        simulation.step();
+        replay.step();
+        assert_eq!(ids(&simulation), ids(&replay));
+    }
+
+    #[test]
+    fn zero_undo_limit_keeps_no_snapshots() {
+        let mut simulation = FishSimulation::from_config(SimConfig::default());
+        simulation.step_n(3);
+        assert!(simulation.can_step_back());
+
+        simulation.undo_limit = 0;
+        simulation.step();
+        assert!(simulation.snapshots.is_empty());
+        assert!(!simulation.step_back());
+    }
+
+    #[test]
+    fn spatial_queries_match_brute_force() {
+        let mut simulation = FishSimulation::new_with_seed(10_000, 0.1, 10, 5, 42, DEFAULT_MAX_AGE);
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        for fish in &mut simulation.fish {
+            (fish.x, fish.y) = (rng.random_range(0..96), rng.random_range(0..64));
+        }
+        simulation.rebuild_cell_index();
+        assert_eq!(simulation.population_count(), 10_000);
+
+        let sorted = |mut ids: Vec<usize>| {
+            ids.sort_unstable();
+            ids
+        };
+        for (x, y) in [(10, 10), (0, 0), (50, 30), (95, 63)] {
+            let expected: Vec<usize> = simulation
+                .alive_fish()
+                .into_iter()
+                .filter(|f| (f.x, f.y) == (x, y))
+                .map(|f| f.id)
+                .collect();
+            let found = simulation.fish_at(x, y).iter().map(|f| f.id).collect();
+            assert_eq!(sorted(found), sorted(expected));
+
+            // Small radii walk the search box, large ones the occupied cells
+            for radius in [0.0, 1.5, 4.0, 200.0] {
+                let expected = simulation
+                    .alive_fish()
+                    .into_iter()
+                    .filter(|f| (f.x as f64 - x as f64).hypot(f.y as f64 - y as f64) <= radius)
+                    .map(|f| f.id)
+                    .collect();
+                let found = simulation
+                    .fish_within(x, y, radius)
+                    .iter()
+                    .map(|f| f.id)
+                    .collect();
+                assert_eq!(
+                    sorted(found),
+                    sorted(expected),
+                    "({}, {}) r {}",
+                    x,
+                    y,
+                    radius
+                );
+            }
+        }
+
+        assert!(simulation.fish_within(10, 10, f64::NAN).is_empty());
+        assert!(simulation.fish_within(10, 10, -1.0).is_empty());
+    }
+
+    #[test]
+    fn cohesion_pulls_fish_together() {
+        let variance_after = |cohesion: f64| {
+            let map = TopographicMap::new(42, 96, 64, 0.12);
+            let mut simulation = FishSimulation::new_on_map(map, 60, 0.0, 0, 0, 42, 1000);
+            simulation.cohesion = cohesion;
+            // Released together, so any spread comes from the walk
+            for fish in &mut simulation.fish {
+                (fish.x, fish.y) = (10, 10);
+            }
+            simulation.rebuild_cell_index();
+            simulation.step_n(40);
+
+            let fish = simulation.alive_fish();
+            let count = fish.len() as f64;
+            let mean_x = fish.iter().map(|f| f.x as f64).sum::<f64>() / count;
+            let mean_y = fish.iter().map(|f| f.y as f64).sum::<f64>() / count;
+            fish.iter()
+                .map(|f| (f.x as f64 - mean_x).powi(2) + (f.y as f64 - mean_y).powi(2))
+                .sum::<f64>()
+                / count
+        };
+
+        let (loose, schooled) = (variance_after(0.0), variance_after(5.0));
+        assert!(schooled < loose, "{} >= {}", schooled, loose);
+    }
+
+    #[test]
+    fn deep_preferring_fish_hold_deeper() {
+        let map = TopographicMap::new(42, 96, 64, 0.12);
+        let mut simulation = FishSimulation::new_on_map(map, 0, 0.0, 0, 0, 42, 1000);
+        simulation.spawn_species(Species::Bluegill, 50);
+        simulation.spawn_species(Species::Crappie, 50);
+        simulation.step_n(40);
+
+        let map = simulation.map().expect("Simulation must have a map");
+        let mean_depth = |species: Species| {
+            let depths: Vec<f64> = simulation
+                .alive_fish()
+                .into_iter()
+                .filter(|f| f.species == species)
+                .map(|f| {
+                    map.water_at(f.x, f.y)
+                        .expect("Fish must be in water")
+                        .depth()
+                        .value()
+                })
+                .collect();
+            depths.iter().sum::<f64>() / depths.len() as f64
+        };
+
+        let (shallow, deep) = (mean_depth(Species::Bluegill), mean_depth(Species::Crappie));
+        assert!(deep > shallow + 2.0, "{} vs {}", deep, shallow);
+    }
+
+    #[test]
+    fn epidemic_rises_then_falls() {
+        let outbreak = |transmission_rate: f64| {
+            // Crowded onto a small map so each case has plenty of contacts
+            let mut simulation = FishSimulation::new_on_map(small_map(), 300, 0.0, 0, 0, 7, 1000);
+            simulation.set_transmission_rate(transmission_rate);
+            assert_eq!(simulation.infect(1), 1);
+            simulation.step_n(60);
+            simulation.infected_history().to_vec()
+        };
+
+        let infected = outbreak(0.9);
+        let peak = infected.iter().copied().max().unwrap_or(0);
+        let peak_tick = infected
+            .iter()
+            .position(|&count| count == peak)
+            .unwrap_or(0);
+        assert_eq!(infected[0], 1);
+        assert!(peak > 10, "{:?}", infected);
+        assert!(peak_tick > 0);
+        assert!(*infected.last().unwrap_or(&0) < peak / 2, "{:?}", infected);
+
+        // Without transmission the first fish is the only case, until it dies
+        // or recovers
+        let infected = outbreak(0.0);
+        assert_eq!(infected[0], 1);
+        assert!(infected.iter().all(|&count| count <= 1));
+        assert!(infected.windows(2).all(|pair| pair[1] <= pair[0]));
+    }
+
+    #[test]
+    fn immigration_keeps_refilling_an_empty_lake() {
+        let mut simulation = FishSimulation::new_with_seed(20, 1.0, 0, 0, 42, 1000);
+        simulation.set_immigration_rate(3.0);
+        simulation.step_n(200);
+
+        let longest_empty = simulation
+            .history()
+            .split(|&population| population > 0)
+            .map(<[usize]>::len)
+            .max()
+            .unwrap_or(0);
+        assert!(longest_empty <= 2, "{:?}", simulation.history());
+
+        let mut closed = FishSimulation::new_with_seed(20, 1.0, 0, 0, 42, 1000);
+        closed.step_n(200);
+        assert!(
+            closed.history()[1..]
+                .iter()
+                .all(|&population| population == 0)
+        );
+    }
+
+    #[test]
+    fn size_selective_harvest_shrinks_the_growth_gene() {
+        let mut simulation = FishSimulation::new_with_seed(200, 0.1, 0, 0, 42, 10);
+        simulation.growth = GrowthModel::Reproduction {
+            breeding_age: 1,
+            fecundity: 0.6,
+        };
+        assert_eq!(simulation.mean_gene(), 1.0);
+
+        // Fast growers pass the size limit younger, so they breed fewer times
+        simulation.set_harvest_rate(0.2);
+        simulation.harvest_min_kg = 0.05;
+        simulation.step_n(80);
+
+        assert!(simulation.population_count() > 0);
+        assert!(simulation.mean_gene() < 0.95, "{}", simulation.mean_gene());
+    }
+
+    #[test]
+    fn cohorts_keep_their_birth_tick() {
+        let mut simulation = FishSimulation::new_with_seed(20, 0.0, 21, 5, 42, 100);
+        assert_eq!(simulation.cohort_sizes(), BTreeMap::from([(0, 20)]));
+
+        simulation.step_n(3);
+        let cohorts = simulation.cohort_sizes();
+        assert_eq!(cohorts, BTreeMap::from([(0, 20), (1, 5)]));
+
+        simulation.spawn_threshold = 0;
+        simulation.set_death_rate(0.3);
+        simulation.step();
+        let shrunk = simulation.cohort_sizes();
+        assert!(shrunk.values().sum::<usize>() < 25);
+        assert_eq!(
+            shrunk.values().sum::<usize>(),
+            simulation.population_count()
+        );
+        for (born_at, count) in shrunk {
+            assert!(count <= cohorts[&born_at]);
+        }
+    }
+
+    #[test]
+    fn is_extinct_flips_with_the_first_empty_tick() {
+        let mut simulation = FishSimulation::new_with_seed(30, 0.3, 0, 0, 42, 100);
+        assert!(!simulation.is_extinct());
+
+        while !simulation.is_extinct() {
+            assert_ne!(simulation.population_count(), 0);
+            simulation.step();
+        }
+
+        assert_eq!(simulation.population_count(), 0);
+        let first_empty = simulation
+            .history()
+            .iter()
+            .position(|&population| population == 0);
+        assert_eq!(first_empty, Some(simulation.history().len() - 1));
+        assert_eq!(simulation.extinct_at(), first_empty);
+    }
+
+    #[test]
+    fn crowded_fish_strip_vegetation_and_empty_lakes_regrow() {
+        fn total_density(simulation: &FishSimulation) -> f64 {
+            simulation
+                .map()
+                .expect("Simulation must have a map")
+                .total_vegetation_density()
+        }
+
+        let densities = |initial_count: usize| {
+            let mut simulation =
+                FishSimulation::new_on_map(small_map(), initial_count, 0.0, 0, 0, 42, 1000);
+            let mut densities = vec![total_density(&simulation)];
+            for _ in 0..20 {
+                simulation.step();
+                densities.push(total_density(&simulation));
+            }
+            densities
+        };
+
+        let crowded = densities(1000);
+        assert!(crowded[20] < crowded[0] / 2.0, "{:?}", crowded);
+
+        let empty = densities(0);
+        assert!(
+            empty.windows(2).all(|pair| pair[1] >= pair[0]),
+            "{:?}",
+            empty
+        );
+        assert!(empty[20] > empty[0]);
+    }
+
+    #[test]
+    fn dead_count_complements_the_population() {
+        let mut simulation = FishSimulation::new_with_seed(30, 0.2, 15, 5, 42, 8);
+        for _ in 0..40 {
+            simulation.step();
+
+            // Every fish ever spawned is either alive or counted dead
+            assert_eq!(
+                simulation.dead_count() + simulation.population_count(),
+                simulation.next_id
+            );
+        }
+
+        let deaths = simulation
+            .events
+            .iter()
+            .filter(|(_, event)| matches!(event, SimEvent::Death { .. } | SimEvent::Eaten { .. }))
+            .count();
+        assert_eq!(simulation.dead_count(), deaths);
+        assert!(simulation.dead_count() > 30);
+    }
+
+    #[test]
+    fn single_sex_population_has_no_offspring() {
+        let mut simulation = FishSimulation::new_with_seed(40, 0.0, 0, 0, 42, 100);
+        simulation.growth = GrowthModel::Reproduction {
+            breeding_age: 0,
+            fecundity: 5.0,
+        };
+        for fish in &mut simulation.fish {
+            fish.sex = Sex::Female;
+        }
+        simulation.step_n(10);
+
+        assert_eq!(simulation.sex_ratio(), 0.0);
+        assert_eq!(simulation.history(), [40; 11]);
+        assert!(
+            !simulation
+                .events()
+                .iter()
+                .any(|(_, event)| matches!(event, SimEvent::Spawn { .. }))
+        );
+    }
+
+    #[test]
+    fn stocking_adds_exactly_the_count_as_newborns() {
+        let mut simulation = FishSimulation::new_on_map(small_map(), 30, 0.0, 0, 0, 42, 100);
+        simulation.step_n(3);
+        let (x, y, _) = simulation
+            .map()
+            .unwrap()
+            .cells()
+            .find(|(_, _, region)| !region.is_land())
+            .unwrap();
+
+        for at in [Some((x, y)), None] {
+            let before = simulation.population_count();
+            let first_id = simulation.fish.len();
+            simulation.stock(50, Species::Bass, at);
+
+            assert_eq!(simulation.population_count(), before + 50);
+            let stocked = &simulation.fish[first_id..];
+            assert_eq!(stocked.len(), 50);
+            for fish in stocked {
+                assert_eq!(fish.age, 0);
+                assert_eq!(fish.species, Species::Bass);
+                assert!(at.is_none_or(|at| (fish.x, fish.y) == at), "{:?}", fish);
+                assert!(simulation.map().unwrap().water_at(fish.x, fish.y).is_some());
+            }
+        }
+
+        let stocked_events = simulation
+            .events()
+            .iter()
+            .filter(|(_, event)| matches!(event, SimEvent::Stocked { .. }))
+            .count();
+        assert_eq!(stocked_events, 100);
+    }
+
+    #[test]
+    fn history_limit_keeps_the_latest_ticks_and_events() {
+        let mut full = FishSimulation::new_with_seed(20, 0.1, 10, 5, 42, DEFAULT_MAX_AGE);
+        let mut limited = full.clone();
+        limited.history_limit = Some(100);
+        full.step_n(500);
+        limited.step_n(500);
+
+        assert_eq!(limited.history().len(), 100);
+        assert_eq!(
+            limited.history(),
+            &full.history()[full.history().len() - 100..]
+        );
+        assert_eq!(limited.history_start(), 401);
+        assert_eq!(limited.tick(), full.tick());
+
+        let latest_events: Vec<_> = full
+            .events()
+            .iter()
+            .filter(|(tick, _)| *tick >= 401)
+            .cloned()
+            .collect();
+        assert!(!latest_events.is_empty());
+        assert_eq!(limited.events(), latest_events.as_slice());
+
+        // Undo still lines the events up with the restored tick
+        limited.step_back();
+        full.step_back();
+        assert_eq!(limited.events().last(), full.events().last());
+    }
+
+    #[test]
+    fn predators_starve_without_prey_and_survive_with_it() {
+        // Without a map every fish shares one cell, so prey is always in reach
+        let predators_after = |prey_per_step: usize| {
+            let mut simulation = FishSimulation::new_with_seed(0, 0.0, 0, 0, 42, 100);
+            simulation.spawn_species(Species::Bass, 10);
+            simulation.set_predator(Some(Species::Bass));
+            for _ in 0..2 * STARVATION_STEPS {
+                simulation.stock(prey_per_step, Species::Bluegill, None);
+                simulation.step();
+            }
+
+            simulation
+        };
+
+        let starved = predators_after(0);
+        assert_eq!(starved.predator_count(), 0);
+        assert_eq!(starved.predator_history().last(), Some(&0));
+
+        let fed = predators_after(10);
+        assert_eq!(fed.predator_count(), 10);
+        assert!(fed.predator_history().iter().all(|&count| count == 10));
+        assert!(
+            fed.events()
+                .iter()
+                .any(|(_, event)| matches!(event, SimEvent::Eaten { .. }))
+        );
+    }
+}
@@ -1,10 +1,17 @@
 use colored::Colorize;
+use image::{ImageError, ImageFormat, Rgb, RgbImage};
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::fmt::Display;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
 use std::vec::Vec;
 
-use noise::{NoiseFn, Perlin};
+use noise::core::worley::ReturnType;
+use noise::{NoiseFn, Perlin, Worley};
 
 const DEPTH_MIN: f64 = 0.0f64;
 const DEPTH_MAX: f64 = 15.0f64;
@@ -12,29 +19,95 @@ const NOISE_MIN: f64 = -1.0f64;
 const NOISE_LAND_MIN: f64 = NOISE_MIN + 0.5f64; // (-0.5,-1.0] is considered land
 const NOISE_MAX: f64 = 1.0f64;
 
+// Land elevation splits between beach, ordinary land and high ground
+const BEACH_ELEVATION_MAX: f64 = 0.15f64;
+const HIGH_GROUND_ELEVATION_MIN: f64 = 0.6f64;
+
+// Worley distance below which a cell counts as near a structure feature point
+const STRUCTURE_WORLEY_RADIUS: f64 = 0.25f64;
+
+const FNV_OFFSET_BASIS: u32 = 0x811c9dc5;
+const FNV_PRIME: u32 = 0x01000193;
+const FNV64_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV64_PRIME: u64 = 0x100000001b3;
+
+const CSV_LAND_SENTINEL: &str = "-1";
+
+// How strongly a current piles mats against the downwind shore. At 1.0 a
+// cell with land straight downwind doubles its mat rate and one with land
+// straight upwind loses it entirely.
+const MAT_DRIFT_STRENGTH: f64 = 1.0f64;
+
+// Depth of the water carve_channels cuts through land
+const CHANNEL_DEPTH: f64 = 1.0f64;
+
+// fishing_hotspots score weights and the depth change that counts as a ledge
+const HOTSPOT_WEEDLINE_WEIGHT: f64 = 1.0f64;
+const HOTSPOT_DROPOFF_WEIGHT: f64 = 1.0f64;
+const HOTSPOT_STRUCTURE_WEIGHT: f64 = 1.5f64;
+const HOTSPOT_DROPOFF_GRADIENT: f64 = 2.0f64;
+
+// Water temperature in degrees Celsius above and below the thermocline, which
+// blends the two over roughly THERMOCLINE_WIDTH of depth
+const SURFACE_TEMPERATURE: f64 = 24.0f64;
+const DEEP_TEMPERATURE: f64 = 8.0f64;
+const DEFAULT_THERMOCLINE_DEPTH: f64 = 6.0f64;
+const THERMOCLINE_WIDTH: f64 = 1.0f64;
+const TEMPERATURE_NOISE_AMPLITUDE: f64 = 1.5f64;
+
+// Rooted vegetation grows no deeper than clarity * DEPTH_MAX. Clarity varies
+// around the map's base clarity on a slow noise layer.
+const DEFAULT_CLARITY: f64 = 1.0f64;
+const CLARITY_NOISE_AMPLITUDE: f64 = 0.15f64;
+const CLARITY_NOISE_FREQUENCY: f64 = 0.25f64;
+
+const HEIGHTMAP_LAND: [u8; 3] = [34, 139, 34];
+const HEIGHTMAP_SHALLOW: [u8; 3] = [173, 216, 230];
+const HEIGHTMAP_DEEP: [u8; 3] = [0, 0, 80];
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DepthRange {
     pub min: f64,
     pub max: f64,
-    pub vegetation_rates: [VegetationRate; 3],
+    /// Borrowed for the built-in table, owned once a table is loaded.
+    pub vegetation_rates: Cow<'static, [VegetationRate]>,
+    pub structure_rates: Cow<'static, [StructureRate]>,
     pub name: DepthRangeName,
 }
 
 impl DepthRange {
     pub fn get_vegetation_rate(&self, veg: &Vegetation, adjacent: bool) -> f64 {
-        let rates = self
-            .vegetation_rates
-            .iter()
-            .find(|x| matches!(&x.vegetation, veg))
-            .expect("Vegetation must be present");
+        let rates = self.vegetation_rates.iter().find(|x| x.vegetation == *veg);
 
-        if adjacent {
-            rates.adjacency_rate
-        } else {
-            rates.rate
+        // Vegetation missing from a range never grows there
+        match rates {
+            Some(rates) if adjacent => rates.adjacency_rate,
+            Some(rates) => rates.rate,
+            None => 0.0f64,
+        }
+    }
+
+    pub fn get_structure_rate(&self, struc: &Structure, adjacent: bool) -> f64 {
+        let rates = self.structure_rates.iter().find(|x| x.structure == *struc);
+
+        // Structure missing from a range never appears there
+        match rates {
+            Some(rates) if adjacent => rates.adjacency_rate,
+            Some(rates) => rates.rate,
+            None => 0.0f64,
         }
     }
+
+    /// Ranges are half-open so a shared boundary belongs to the deeper range.
+    /// Depth::depth_range_in also gives the deepest range of a table its max.
+    pub fn contains(&self, depth: f64) -> bool {
+        depth >= self.min && depth < self.max
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DepthRangeName {
     SuperShallow,
     Shallow,
@@ -42,16 +115,42 @@ pub enum DepthRangeName {
     Deep,
 }
 
-impl Display for DepthRange {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let symbol = match self.name {
+impl DepthRangeName {
+    pub const ALL: [DepthRangeName; 4] = [
+        DepthRangeName::SuperShallow,
+        DepthRangeName::Shallow,
+        DepthRangeName::MidDepth,
+        DepthRangeName::Deep,
+    ];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
             DepthRangeName::Deep => "█",
             DepthRangeName::MidDepth => "▓",
             DepthRangeName::Shallow => "▒",
             DepthRangeName::SuperShallow => "░",
-        };
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DepthRangeName::Deep => "deep",
+            DepthRangeName::MidDepth => "mid-depth",
+            DepthRangeName::Shallow => "shallow",
+            DepthRangeName::SuperShallow => "super-shallow",
+        }
+    }
+}
+
+impl Display for DepthRangeName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol().blue())
+    }
+}
 
-        write!(f, "{}", symbol.blue())
+impl Display for DepthRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
     }
 }
 
@@ -59,7 +158,7 @@ pub const DEPTH_RANGES: [DepthRange; 4] = [
     DepthRange {
         min: DEPTH_MIN,
         max: 5.0f64,
-        vegetation_rates: [
+        vegetation_rates: Cow::Borrowed(&[
             VegetationRate {
                 vegetation: Vegetation::Grass,
                 rate: 0.1f64,
@@ -75,13 +174,55 @@ pub const DEPTH_RANGES: [DepthRange; 4] = [
                 rate: 0.1f64,
                 adjacency_rate: 0.75f64,
             },
-        ],
+            VegetationRate {
+                vegetation: Vegetation::Lilypads,
+                rate: 0.08f64,
+                adjacency_rate: 0.6f64,
+            },
+            VegetationRate {
+                vegetation: Vegetation::Hydrilla,
+                rate: 0.02f64,
+                adjacency_rate: 0.10f64,
+            },
+        ]),
+        structure_rates: Cow::Borrowed(&[
+            StructureRate {
+                structure: Structure::ChunkRock,
+                rate: 0.01f64,
+                adjacency_rate: 0.05f64,
+            },
+            StructureRate {
+                structure: Structure::Boulder,
+                rate: 0.01f64,
+                adjacency_rate: 0.05f64,
+            },
+            StructureRate {
+                structure: Structure::Timber,
+                rate: 0.04f64,
+                adjacency_rate: 0.35f64,
+            },
+            StructureRate {
+                structure: Structure::Brush,
+                rate: 0.05f64,
+                adjacency_rate: 0.35f64,
+            },
+            StructureRate {
+                structure: Structure::Dock,
+                rate: 0.02f64,
+                adjacency_rate: 0.15f64,
+            },
+            StructureRate {
+                structure: Structure::Stump,
+                rate: 0.02f64,
+                adjacency_rate: 0.20f64,
+            },
+        ]),
         name: DepthRangeName::SuperShallow,
     },
     DepthRange {
         min: 5.0f64,
         max: 7.0f64,
-        vegetation_rates: [
+        vegetation_rates: Cow::Borrowed(&[
             VegetationRate {
                 vegetation: Vegetation::Grass,
                 rate: 0.2f64,
@@ -97,13 +238,55 @@ pub const DEPTH_RANGES: [DepthRange; 4] = [
                 rate: 0.2f64,
                 adjacency_rate: 0.75f64,
             },
-        ],
+            VegetationRate {
+                vegetation: Vegetation::Lilypads,
+                rate: 0.03f64,
+                adjacency_rate: 0.25f64,
+            },
+            VegetationRate {
+                vegetation: Vegetation::Hydrilla,
+                rate: 0.05f64,
+                adjacency_rate: 0.35f64,
+            },
+        ]),
+        structure_rates: Cow::Borrowed(&[
+            StructureRate {
+                structure: Structure::ChunkRock,
+                rate: 0.02f64,
+                adjacency_rate: 0.10f64,
+            },
+            StructureRate {
+                structure: Structure::Boulder,
+                rate: 0.02f64,
+                adjacency_rate: 0.15f64,
+            },
+            StructureRate {
+                structure: Structure::Timber,
+                rate: 0.03f64,
+                adjacency_rate: 0.30f64,
+            },
+            StructureRate {
+                structure: Structure::Brush,
+                rate: 0.03f64,
+                adjacency_rate: 0.25f64,
+            },
+            StructureRate {
+                structure: Structure::BrushPile,
+                rate: 0.01f64,
+                adjacency_rate: 0.05f64,
+            },
+            StructureRate {
+                structure: Structure::Stump,
+                rate: 0.03f64,
+                adjacency_rate: 0.30f64,
+            },
+        ]),
         name: DepthRangeName::Shallow,
     },
     DepthRange {
         min: 7.0f64,
         max: 10.0f64,
-        vegetation_rates: [
+        vegetation_rates: Cow::Borrowed(&[
             VegetationRate {
                 vegetation: Vegetation::Grass,
                 rate: 0.12f64,
@@ -119,13 +302,45 @@ pub const DEPTH_RANGES: [DepthRange; 4] = [
                 rate: 0.12f64,
                 adjacency_rate: 0.45f64,
             },
-        ],
+            VegetationRate {
+                vegetation: Vegetation::Hydrilla,
+                rate: 0.1f64,
+                adjacency_rate: 0.5f64,
+            },
+        ]),
+        structure_rates: Cow::Borrowed(&[
+            StructureRate {
+                structure: Structure::ChunkRock,
+                rate: 0.04f64,
+                adjacency_rate: 0.25f64,
+            },
+            StructureRate {
+                structure: Structure::Boulder,
+                rate: 0.04f64,
+                adjacency_rate: 0.35f64,
+            },
+            StructureRate {
+                structure: Structure::Timber,
+                rate: 0.01f64,
+                adjacency_rate: 0.10f64,
+            },
+            StructureRate {
+                structure: Structure::Brush,
+                rate: 0.0f64,
+                adjacency_rate: 0.0f64,
+            },
+            StructureRate {
+                structure: Structure::BrushPile,
+                rate: 0.02f64,
+                adjacency_rate: 0.08f64,
+            },
+        ]),
         name: DepthRangeName::MidDepth,
     },
     DepthRange {
         min: 10.0f64,
         max: DEPTH_MAX,
-        vegetation_rates: [
+        vegetation_rates: Cow::Borrowed(&[
             VegetationRate {
                 vegetation: Vegetation::Grass,
                 rate: 0.05f64,
@@ -141,7 +356,34 @@ pub const DEPTH_RANGES: [DepthRange; 4] = [
                 rate: 0.05f64,
                 adjacency_rate: 0.20f64,
             },
-        ],
+            VegetationRate {
+                vegetation: Vegetation::Hydrilla,
+                rate: 0.02f64,
+                adjacency_rate: 0.10f64,
+            },
+        ]),
+        structure_rates: Cow::Borrowed(&[
+            StructureRate {
+                structure: Structure::ChunkRock,
+                rate: 0.03f64,
+                adjacency_rate: 0.25f64,
+            },
+            StructureRate {
+                structure: Structure::Boulder,
+                rate: 0.03f64,
+                adjacency_rate: 0.30f64,
+            },
+            StructureRate {
+                structure: Structure::Timber,
+                rate: 0.0f64,
+                adjacency_rate: 0.0f64,
+            },
+            StructureRate {
+                structure: Structure::Brush,
+                rate: 0.0f64,
+                adjacency_rate: 0.0f64,
+            },
+        ]),
         name: DepthRangeName::Deep,
     },
 ];
@@ -155,131 +397,465 @@ impl Display for DepthError {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapError {
+    ZeroDimensions,
+    InvalidScale,
+    CapacityOverflow,
+    OutOfBounds,
+    InvalidDepthRanges,
+}
+
+impl Display for MapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapError::ZeroDimensions => write!(f, "Map width and height must be non-zero"),
+            MapError::InvalidScale => write!(f, "Map scale must be finite and positive"),
+            MapError::CapacityOverflow => write!(f, "Map width * height overflows usize"),
+            MapError::OutOfBounds => write!(f, "Region exceeds the map bounds"),
+            MapError::InvalidDepthRanges => write!(
+                f,
+                "Depth ranges must be contiguous and cover every depth from {} to {}",
+                DEPTH_MIN, DEPTH_MAX
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NoiseDepth(f64);
 
 impl NoiseDepth {
     pub fn is_land(&self) -> bool {
-        self.0 < NOISE_LAND_MIN
+        self.is_land_with(NOISE_LAND_MIN)
+    }
+
+    /// Noise lies in [-1.0, 1.0], so thresholds outside that range produce
+    /// all-water or all-land maps.
+    pub fn is_land_with(&self, land_threshold: f64) -> bool {
+        self.0 < land_threshold
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Depth(f64);
 
 impl Depth {
-    fn depth_range(&self) -> &DepthRange {
-        DEPTH_RANGES
+    pub fn value(&self) -> f64 {
+        self.0
+    }
+
+    pub fn is_land(&self) -> bool {
+        self.0 < DEPTH_MIN
+    }
+
+    /// The range in a sorted table holding this depth, None for land. The
+    /// deepest range is closed, so the table's maximum depth falls in it.
+    pub fn depth_range_in<'a>(&self, ranges: &'a [DepthRange]) -> Option<&'a DepthRange> {
+        ranges
             .iter()
-            .find(|x| self.0 >= x.min && self.0 <= x.max)
-            .expect("Depth range must exist")
+            .find(|x| x.contains(self.0))
+            .or_else(|| ranges.last().filter(|last| self.0 == last.max))
     }
-}
 
-impl From<NoiseDepth> for Depth {
-    fn from(noise_value: NoiseDepth) -> Self {
-        let converted_value = match noise_value.is_land() {
-            true => panic!("Depths that are land are no longer supported. ToDo: refactor this"),
-            false => {
-                (noise_value.0 - NOISE_LAND_MIN) / (NOISE_MAX - NOISE_LAND_MIN)
-                    * (DEPTH_MAX - DEPTH_MIN)
-                    + DEPTH_MIN
-            }
+    /// Stratified water temperature at this depth, ignoring local variation.
+    pub fn temperature(&self, thermocline_depth: f64) -> f64 {
+        let warm_share =
+            1.0f64 / (1.0f64 + ((self.0 - thermocline_depth) / THERMOCLINE_WIDTH).exp());
+        DEEP_TEMPERATURE + (SURFACE_TEMPERATURE - DEEP_TEMPERATURE) * warm_share
+    }
+
+    pub fn symbol_in(&self, ranges: &[DepthRange]) -> &'static str {
+        match self.depth_range_in(ranges) {
+            Some(range) => range.name.symbol(),
+            None => TopographicLandRegion::SYMBOL,
+        }
+    }
+
+    fn heightmap_pixel(&self) -> Rgb<u8> {
+        if self.is_land() {
+            return Rgb(HEIGHTMAP_LAND);
+        }
+
+        let t = ((self.0 - DEPTH_MIN) / (DEPTH_MAX - DEPTH_MIN)).clamp(0.0f64, 1.0f64);
+        let channel = |i: usize| {
+            let shallow = HEIGHTMAP_SHALLOW[i] as f64;
+            let deep = HEIGHTMAP_DEEP[i] as f64;
+            (shallow + (deep - shallow) * t).round() as u8
         };
 
+        Rgb([channel(0), channel(1), channel(2)])
+    }
+}
+
+impl Depth {
+    fn from_noise(noise_value: NoiseDepth, land_threshold: f64, depth_bounds: (f64, f64)) -> Self {
+        let (depth_min, depth_max) = depth_bounds;
+
+        // Only noise right at the top of the scale is water with the threshold
+        // there, and it's as shallow as water gets
+        let band = NOISE_MAX - land_threshold;
+        if band <= 0.0f64 {
+            return Self(depth_min);
+        }
+
+        // Land noise maps below the minimum depth, which is_land() picks up
+        let converted_value =
+            (noise_value.0 - land_threshold) / band * (depth_max - depth_min) + depth_min;
+
         Self(converted_value)
     }
 }
 
-impl Display for Depth {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.depth_range())
+impl From<NoiseDepth> for Depth {
+    fn from(noise_value: NoiseDepth) -> Self {
+        Self::from_noise(noise_value, NOISE_LAND_MIN, (DEPTH_MIN, DEPTH_MAX))
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BottomComposition {
     Mud,
     Hard,
     Gravel,
 }
 
+impl BottomComposition {
+    // Deep basins collect silt and shallow flats get washed down to gravel, with
+    // the bottom noise layer breaking up the bands
+    fn from_depth(depth: &Depth, ranges: &[DepthRange], bottom_noise: f64) -> Self {
+        let depth_bias = match depth.depth_range_in(ranges).map(|x| x.name) {
+            Some(DepthRangeName::SuperShallow) => -0.4f64,
+            Some(DepthRangeName::Shallow) => -0.15f64,
+            Some(DepthRangeName::MidDepth) | None => 0.0f64,
+            Some(DepthRangeName::Deep) => 0.4f64,
+        };
+
+        let value = depth_bias + bottom_noise * 0.5f64;
+        if value < -0.2f64 {
+            BottomComposition::Gravel
+        } else if value > 0.2f64 {
+            BottomComposition::Mud
+        } else {
+            BottomComposition::Hard
+        }
+    }
+
+    /// Multiplier on vegetation rates. Rooted plants struggle on hard and
+    /// gravel bottoms, while mats float and care less about the substrate.
+    pub fn vegetation_factor(&self, vegetation: &Vegetation) -> f64 {
+        match (self, vegetation) {
+            (BottomComposition::Mud, _) => 1.0f64,
+            (BottomComposition::Gravel, Vegetation::Grass) => 0.6f64,
+            (BottomComposition::Gravel, Vegetation::Reeds) => 0.7f64,
+            (BottomComposition::Gravel, Vegetation::Mats) => 0.9f64,
+            (BottomComposition::Gravel, Vegetation::Lilypads) => 0.5f64,
+            (BottomComposition::Gravel, Vegetation::Hydrilla) => 0.6f64,
+            (BottomComposition::Hard, Vegetation::Grass) => 0.3f64,
+            (BottomComposition::Hard, Vegetation::Reeds) => 0.4f64,
+            (BottomComposition::Hard, Vegetation::Mats) => 0.8f64,
+            (BottomComposition::Hard, Vegetation::Lilypads) => 0.2f64,
+            (BottomComposition::Hard, Vegetation::Hydrilla) => 0.3f64,
+        }
+    }
+}
+
+impl Display for BottomComposition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match *self {
+            BottomComposition::Mud => "Mud",
+            BottomComposition::Hard => "Hard",
+            BottomComposition::Gravel => "Gravel",
+        };
+
+        write!(f, "{}", text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VegetationRate {
     vegetation: Vegetation,
     rate: f64,
     adjacency_rate: f64,
 }
 
+impl VegetationRate {
+    /// adjacency_rate applies when a neighboring cell already has the vegetation.
+    pub const fn new(vegetation: Vegetation, rate: f64, adjacency_rate: f64) -> Self {
+        Self {
+            vegetation,
+            rate,
+            adjacency_rate,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Vegetation {
     Grass,
     Reeds,
     Mats,
+    Lilypads,
+    Hydrilla,
+}
+
+impl Vegetation {
+    pub const ALL: [Vegetation; 5] = [
+        Vegetation::Grass,
+        Vegetation::Reeds,
+        Vegetation::Mats,
+        Vegetation::Lilypads,
+        Vegetation::Hydrilla,
+    ];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Vegetation::Grass => "„",
+            Vegetation::Reeds => "¥",
+            Vegetation::Mats => "¬",
+            Vegetation::Lilypads => "ø",
+            Vegetation::Hydrilla => "§",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Vegetation::Grass => "grass",
+            Vegetation::Reeds => "reeds",
+            Vegetation::Mats => "mats",
+            Vegetation::Lilypads => "lilypads",
+            Vegetation::Hydrilla => "hydrilla",
+        }
+    }
+
+    /// Everything but mats, which float.
+    pub fn is_rooted(&self) -> bool {
+        !matches!(self, Vegetation::Mats)
+    }
+
+    // Rooted vegetation needs light to reach the bottom, which it does down
+    // to the local clarity's share of DEPTH_MAX
+    fn too_dark(&self, depth: &Depth, clarity: f64) -> bool {
+        self.is_rooted() && depth.value() > clarity * DEPTH_MAX
+    }
 }
 
 impl Display for Vegetation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let text = match *self {
-            Vegetation::Grass => "„".green(),
-            Vegetation::Reeds => "¥".green(),
-            Vegetation::Mats => "¬".green(),
-        };
+        write!(f, "{}", self.symbol().green())
+    }
+}
 
-        write!(f, "{}", text)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Season {
+    Spring,
+    Summer,
+    Fall,
+    Winter,
+}
+
+impl Season {
+    pub const ALL: [Season; 4] = [Season::Spring, Season::Summer, Season::Fall, Season::Winter];
+
+    /// Chance that each vegetation patch dies back.
+    fn dieback(&self) -> f64 {
+        match self {
+            Season::Spring | Season::Summer => 0.0f64,
+            Season::Fall => 0.25f64,
+            Season::Winter => 0.5f64,
+        }
+    }
+
+    /// Chance that a bare cell next to vegetation is colonized by it.
+    fn spread(&self) -> f64 {
+        match self {
+            Season::Spring => 0.15f64,
+            Season::Summer => 0.3f64,
+            Season::Fall | Season::Winter => 0.0f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VegetationPatch {
+    pub vegetation: Vegetation,
+    /// How thick the bed is, from 0.0 (bare) to 1.0 (fully matted).
+    pub density: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StructureRate {
+    structure: Structure,
+    rate: f64,
+    adjacency_rate: f64,
+}
+
+impl StructureRate {
+    /// adjacency_rate applies when the cell is next to matching structure or,
+    /// for shore structure, to land.
+    pub const fn new(structure: Structure, rate: f64, adjacency_rate: f64) -> Self {
+        Self {
+            structure,
+            rate,
+            adjacency_rate,
+        }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Structure {
     ChunkRock,
     Boulder,
     Timber,
     Brush,
+    Dock,
+    BrushPile,
+    Stump,
+}
+
+impl Structure {
+    pub const ALL: [Structure; 7] = [
+        Structure::ChunkRock,
+        Structure::Boulder,
+        Structure::Timber,
+        Structure::Brush,
+        Structure::Dock,
+        Structure::BrushPile,
+        Structure::Stump,
+    ];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Structure::ChunkRock => "¤",
+            Structure::Boulder => "®",
+            Structure::Timber => "˜",
+            Structure::Brush => "×",
+            Structure::Dock => "Π",
+            Structure::BrushPile => "ж",
+            Structure::Stump => "ı",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Structure::ChunkRock => "chunk rock",
+            Structure::Boulder => "boulder",
+            Structure::Timber => "timber",
+            Structure::Brush => "brush",
+            Structure::Dock => "dock",
+            Structure::BrushPile => "brush pile",
+            Structure::Stump => "stump",
+        }
+    }
 }
 
 impl Display for Structure {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let text = match *self {
-            Structure::ChunkRock => "¤".red(),
-            Structure::Boulder => "®".red(),
-            Structure::Timber => "˜".yellow(),
-            Structure::Brush => "×".yellow(),
+            Structure::ChunkRock | Structure::Boulder => self.symbol().red(),
+            Structure::Timber | Structure::Brush => self.symbol().yellow(),
+            Structure::Dock => self.symbol().white(),
+            Structure::BrushPile => self.symbol().magenta(),
+            Structure::Stump => self.symbol().bright_yellow(),
         };
 
         write!(f, "{}", text)
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TopographicRegion {
     Land(TopographicLandRegion),
     Water(TopographicWaterRegion),
 }
 
-impl Display for TopographicRegion {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl TopographicRegion {
+    pub fn is_land(&self) -> bool {
+        matches!(self, Self::Land(_))
+    }
+
+    pub fn symbol_in(&self, ranges: &[DepthRange]) -> &'static str {
         match self {
-            Self::Land(land) => land.fmt(f),
-            Self::Water(water) => water.fmt(f),
+            Self::Land(land) => land.symbol(),
+            Self::Water(water) => water.symbol_in(ranges),
         }
     }
 }
 
-pub struct TopographicLandRegion {}
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TopographicLandRegion {
+    elevation: f64,
+}
+
+impl TopographicLandRegion {
+    pub const SYMBOL: &'static str = "#";
+    pub const BEACH_SYMBOL: &'static str = ".";
+    pub const HIGH_GROUND_SYMBOL: &'static str = "▲";
+
+    /// Elevation runs from 0.0 at the waterline to 1.0 and is clamped.
+    pub fn new(elevation: f64) -> Self {
+        Self {
+            elevation: elevation.clamp(0.0f64, 1.0f64),
+        }
+    }
+
+    pub fn elevation(&self) -> f64 {
+        self.elevation
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        if self.elevation < BEACH_ELEVATION_MAX {
+            Self::BEACH_SYMBOL
+        } else if self.elevation < HIGH_GROUND_ELEVATION_MIN {
+            Self::SYMBOL
+        } else {
+            Self::HIGH_GROUND_SYMBOL
+        }
+    }
+
+    // How far noise lies past the land threshold, as a share of the land band
+    fn from_noise(noise_value: NoiseDepth, land_threshold: f64) -> Self {
+        let band = land_threshold - NOISE_MIN;
+        if band <= 0.0f64 {
+            return Self::new(0.0f64);
+        }
+
+        Self::new((land_threshold - noise_value.0) / band)
+    }
+}
 
 impl Display for TopographicLandRegion {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "#")
+        write!(f, "{}", self.symbol())
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TopographicWaterRegion {
     bottom: BottomComposition,
-    vegetation: Option<Vegetation>,
+    vegetation: Vec<VegetationPatch>,
     structure: Option<Structure>,
     depth: Depth,
+    temperature: f64,
+    clarity: f64,
 }
 
 impl TopographicWaterRegion {
     pub fn new(
         bottom: BottomComposition,
-        vegetation: Option<Vegetation>,
+        vegetation: Vec<VegetationPatch>,
         structure: Option<Structure>,
         depth: Depth,
     ) -> Self {
@@ -287,162 +863,3715 @@ impl TopographicWaterRegion {
             bottom,
             vegetation,
             structure,
+            temperature: depth.temperature(DEFAULT_THERMOCLINE_DEPTH),
+            clarity: DEFAULT_CLARITY,
             depth,
         }
     }
 
+    /// Overrides the default full clarity set in new.
+    pub fn with_clarity(mut self, clarity: f64) -> Self {
+        self.clarity = clarity.clamp(0.0f64, 1.0f64);
+        self
+    }
+
+    /// Overrides the temperature derived from depth in new.
+    pub fn with_temperature(mut self, temperature: f64) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn bottom(&self) -> &BottomComposition {
+        &self.bottom
+    }
+
+    pub fn depth(&self) -> &Depth {
+        &self.depth
+    }
+
+    /// Degrees Celsius.
+    pub fn temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    /// From 0.0 (murky) to 1.0 (clear).
+    pub fn clarity(&self) -> f64 {
+        self.clarity
+    }
+
+    pub fn vegetation(&self) -> &[VegetationPatch] {
+        &self.vegetation
+    }
+
+    pub fn structure(&self) -> Option<&Structure> {
+        self.structure.as_ref()
+    }
+
     pub fn has_vegetation_type(&self, vegetation_type: &Vegetation) -> bool {
-        if let Some(veg) = &self.vegetation {
-            matches!(veg, vegetation_type)
+        self.vegetation
+            .iter()
+            .any(|patch| patch.vegetation == *vegetation_type)
+    }
+
+    /// Density of the thickest vegetation in the cell, 0.0 when bare.
+    pub fn vegetation_density(&self) -> f32 {
+        self.vegetation
+            .iter()
+            .map(|patch| patch.density)
+            .fold(0.0f32, f32::max)
+    }
+
+    pub fn has_structure_type(&self, structure_type: &Structure) -> bool {
+        self.structure.as_ref() == Some(structure_type)
+    }
+
+    /// Lists every feature of the cell, unlike its glyph which can only show
+    /// one. The depth range is named from the map's table.
+    pub fn describe_in(&self, ranges: &[DepthRange]) -> String {
+        let range = self
+            .depth
+            .depth_range_in(ranges)
+            .map_or("out of range", |range| range.name.label());
+
+        let mut features = vec![
+            format!("{} bottom", self.bottom),
+            format!("depth {:.2} ({})", self.depth.value(), range),
+        ];
+
+        if !self.vegetation.is_empty() {
+            let labels: Vec<&str> = self
+                .vegetation
+                .iter()
+                .map(|patch| patch.vegetation.label())
+                .collect();
+            features.push(format!("vegetation: {}", labels.join(" and ")));
+        }
+
+        if let Some(struc) = &self.structure {
+            features.push(format!("structure: {}", struc.label()));
+        }
+
+        features.join(", ")
+    }
+
+    pub fn symbol_in(&self, ranges: &[DepthRange]) -> &'static str {
+        // Mixed beds show their first vegetation type
+        if let Some(patch) = self.vegetation.first() {
+            patch.vegetation.symbol()
+        } else if let Some(struc) = &self.structure {
+            struc.symbol()
         } else {
-            false
+            self.depth.symbol_in(ranges)
         }
     }
 }
 
-impl Display for TopographicWaterRegion {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Some(veg) = &self.vegetation {
-            write!(f, "{}", veg)
+impl TopographicWaterRegion {
+    // Colored glyph, with bare water shaded by its range in the map's table
+    fn fmt_in(&self, f: &mut std::fmt::Formatter<'_>, ranges: &[DepthRange]) -> std::fmt::Result {
+        if let Some(patch) = self.vegetation.first() {
+            write!(f, "{}", patch.vegetation)
         } else if let Some(struc) = &self.structure {
             write!(f, "{}", struc)
         } else {
-            write!(f, "{}", self.depth)
+            match self.depth.depth_range_in(ranges) {
+                Some(range) => write!(f, "{}", range),
+                None => write!(f, "{}", TopographicLandRegion::SYMBOL),
+            }
         }
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TopographicMap {
     seed: u32,
     width: usize,
     height: usize,
-    scale: f64,
+    /// Noise scale along x and y.
+    scale: (f64, f64),
+    land_threshold: f64,
+    wrap: bool,
+    current: (f64, f64),
+    /// Maps saved before the table was serialized load with the default one.
+    #[cfg_attr(feature = "serde", serde(default = "default_depth_ranges"))]
+    depth_ranges: Vec<DepthRange>,
     data: Vec<TopographicRegion>,
 }
 
 impl TopographicMap {
     pub fn new(seed: u32, width: usize, height: usize, scale: f64) -> Self {
-        let data = generate(seed, width, height, scale);
-        Self {
-            seed,
-            width,
-            height,
-            scale,
-            data,
-        }
+        Self::try_new(seed, width, height, scale).unwrap()
     }
-}
 
-enum AdjacencyDirection {
-    Up,
-    Left,
-}
+    pub fn try_new(seed: u32, width: usize, height: usize, scale: f64) -> Result<Self, MapError> {
+        TopographicMapBuilder::new()
+            .seed(seed)
+            .dimensions(width, height)
+            .scale(scale)
+            .try_build()
+    }
 
-fn get_adjacent(
-    map: &[TopographicRegion],
-    width: usize,
-    x: usize,
-    y: usize,
-    direction: AdjacencyDirection,
-) -> Option<&TopographicRegion> {
-    if y == 0 && matches!(direction, AdjacencyDirection::Up) {
-        return None;
+    /// Like new, but also reports how long each generation phase took.
+    pub fn new_timed(
+        seed: u32,
+        width: usize,
+        height: usize,
+        scale: f64,
+    ) -> (Self, GenerationTimings) {
+        TopographicMapBuilder::new()
+            .seed(seed)
+            .dimensions(width, height)
+            .scale(scale)
+            .try_build_timed()
+            .unwrap()
     }
 
-    if x == 0 && matches!(direction, AdjacencyDirection::Left) {
-        return None;
+    /// Generates the map for a named lake. The name is hashed with 32-bit
+    /// FNV-1a, which is stable across releases, so a name always maps to the
+    /// same seed.
+    pub fn from_seed_str(name: &str, width: usize, height: usize, scale: f64) -> Self {
+        Self::new(seed_from_str(name), width, height, scale)
     }
 
-    let index = match direction {
-        AdjacencyDirection::Up => ((y - 1) * width) + x,
-        AdjacencyDirection::Left => (y * width) + x - 1,
-    };
+    pub fn legend() -> String {
+        let mut lines = vec![
+            format!("{} = beach", TopographicLandRegion::BEACH_SYMBOL),
+            format!("{} = land", TopographicLandRegion::SYMBOL),
+            format!(
+                "{} = high ground",
+                TopographicLandRegion::HIGH_GROUND_SYMBOL
+            ),
+        ];
 
-    Some(map.get(index).expect("Indexed element must exist"))
-}
+        lines.extend(
+            DepthRangeName::ALL
+                .iter()
+                .map(|name| format!("{} = {} water", name, name.label())),
+        );
+        lines.extend(
+            Vegetation::ALL
+                .iter()
+                .map(|veg| format!("{} = {}", veg, veg.label())),
+        );
+        lines.extend(
+            Structure::ALL
+                .iter()
+                .map(|struc| format!("{} = {}", struc, struc.label())),
+        );
 
-fn generate(seed: u32, width: usize, height: usize, scale: f64) -> Vec<TopographicRegion> {
-    let mut rng = ChaCha8Rng::seed_from_u64(seed.into());
+        lines.join("\n")
+    }
 
-    let perlin = Perlin::new(seed);
-    let mut data = Vec::with_capacity(width * height);
+    pub fn builder() -> TopographicMapBuilder {
+        TopographicMapBuilder::new()
+    }
 
-    for y in 0..height {
-        for x in 0..width {
-            let nx = x as f64 * scale;
-            let ny = y as f64 * scale;
-            let noise_depth = NoiseDepth(perlin.get([nx, ny]));
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
 
-            if noise_depth.is_land() {
-                data.push(TopographicRegion::Land(TopographicLandRegion {}));
-            } else {
-                let depth = Depth::from(noise_depth);
+    pub fn width(&self) -> usize {
+        self.width
+    }
 
-                let mut vegetation: Option<Vegetation> = None;
-                let mut structure: Option<Structure> = None;
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn land_threshold(&self) -> f64 {
+        self.land_threshold
+    }
 
-                let up_adjacent = get_adjacent(&data, width, x, y, AdjacencyDirection::Up);
-                let left_adjacent = get_adjacent(&data, width, x, y, AdjacencyDirection::Left);
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
 
-                let veg_type = match rng.random_range(0..3) {
-                    0 => Vegetation::Grass,
-                    1 => Vegetation::Reeds,
-                    2 => Vegetation::Mats,
-                    _ => unreachable!(),
-                };
+    /// Unit vector the current flows toward in grid coordinates (x east, y
+    /// south), or (0.0, 0.0) when there is no current.
+    pub fn current_direction(&self) -> (f64, f64) {
+        self.current
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&TopographicRegion> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        self.data.get((y * self.width) + x)
+    }
+
+    pub fn water_at(&self, x: usize, y: usize) -> Option<&TopographicWaterRegion> {
+        match self.get(x, y)? {
+            TopographicRegion::Land(_) => None,
+            TopographicRegion::Water(water) => Some(water),
+        }
+    }
+
+    /// The depth range table this map was generated with.
+    pub fn depth_ranges(&self) -> &[DepthRange] {
+        &self.depth_ranges
+    }
+
+    /// Range of the water cell in this map's own table, None for land.
+    pub fn range_name_at(&self, x: usize, y: usize) -> Option<DepthRangeName> {
+        self.water_at(x, y)?
+            .depth()
+            .depth_range_in(&self.depth_ranges)
+            .map(|range| range.name)
+    }
+
+    /// Land elevation from 0.0 at the waterline to 1.0, None for water.
+    pub fn elevation_at(&self, x: usize, y: usize) -> Option<f64> {
+        match self.get(x, y)? {
+            TopographicRegion::Land(land) => Some(land.elevation()),
+            TopographicRegion::Water(_) => None,
+        }
+    }
+
+    /// Heightmap color of the cell, shaded by its exact depth for water.
+    pub fn shade_at(&self, x: usize, y: usize) -> Option<Rgb<u8>> {
+        match self.get(x, y)? {
+            TopographicRegion::Land(_) => Some(Rgb(HEIGHTMAP_LAND)),
+            TopographicRegion::Water(water) => Some(water.depth.heightmap_pixel()),
+        }
+    }
+
+    /// Degrees Celsius, None for land.
+    pub fn temperature_at(&self, x: usize, y: usize) -> Option<f64> {
+        self.water_at(x, y).map(|water| water.temperature())
+    }
+
+    /// None for land.
+    pub fn clarity_at(&self, x: usize, y: usize) -> Option<f64> {
+        self.water_at(x, y).map(|water| water.clarity())
+    }
+
+    /// 0.0 for land and bare water.
+    pub fn vegetation_density_at(&self, x: usize, y: usize) -> f32 {
+        self.water_at(x, y)
+            .map_or(0.0f32, |water| water.vegetation_density())
+    }
+
+    /// Sum of every water cell's vegetation density.
+    pub fn total_vegetation_density(&self) -> f64 {
+        self.data
+            .iter()
+            .map(|region| match region {
+                TopographicRegion::Land(_) => 0.0f64,
+                TopographicRegion::Water(water) => water.vegetation_density() as f64,
+            })
+            .sum()
+    }
+
+    /// Thins every vegetation patch in the cell by amount. Patches grazed down
+    /// to 0.0 stay rooted so they can regrow.
+    pub fn graze(&mut self, x: usize, y: usize, amount: f32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        if let TopographicRegion::Water(water) = &mut self.data[(y * self.width) + x] {
+            for patch in &mut water.vegetation {
+                patch.density = (patch.density - amount).max(0.0f32);
+            }
+        }
+    }
+
+    /// Thickens every vegetation patch on the map by rate, up to 1.0.
+    pub fn regrow_vegetation(&mut self, rate: f32) {
+        for region in &mut self.data {
+            if let TopographicRegion::Water(water) = region {
+                for patch in &mut water.vegetation {
+                    patch.density = (patch.density + rate).min(1.0f32);
+                }
+            }
+        }
+    }
+
+    /// Density of every vegetation patch on the map, in cell order. Grazing
+    /// only changes these, so they're enough to undo it.
+    pub fn vegetation_densities(&self) -> Vec<f32> {
+        self.data
+            .iter()
+            .filter_map(|region| match region {
+                TopographicRegion::Land(_) => None,
+                TopographicRegion::Water(water) => Some(&water.vegetation),
+            })
+            .flatten()
+            .map(|patch| patch.density)
+            .collect()
+    }
+
+    /// Restores densities taken from vegetation_densities on this map.
+    pub fn set_vegetation_densities(&mut self, densities: &[f32]) {
+        let patches = self
+            .data
+            .iter_mut()
+            .filter_map(|region| match region {
+                TopographicRegion::Land(_) => None,
+                TopographicRegion::Water(water) => Some(&mut water.vegetation),
+            })
+            .flatten();
+        for (patch, &density) in patches.zip(densities) {
+            patch.density = density;
+        }
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (usize, usize, &TopographicRegion)> {
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(index, region)| (index % self.width, index / self.width, region))
+    }
+
+    /// In-bounds 4-neighbors of a cell, wrapping at the edges on wrapping maps.
+    pub fn orthogonal_neighbors(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> {
+        AdjacencyDirection::ORTHOGONAL
+            .iter()
+            .filter_map(move |direction| direction.apply(x, y, self.width, self.height, self.wrap))
+    }
+
+    /// Magnitude of the depth gradient at each water cell, indexed like the
+    /// map's cells. Uses central differences where both neighbors along an
+    /// axis are water and a one-sided difference where only one is. Land and
+    /// cells with no water neighbors are 0.0.
+    pub fn slope_map(&self) -> Vec<f64> {
+        let depth_toward = |x: usize, y: usize, direction: AdjacencyDirection| {
+            direction
+                .apply(x, y, self.width, self.height, self.wrap)
+                .and_then(|(nx, ny)| self.water_at(nx, ny))
+                .map(|water| water.depth().value())
+        };
+        let difference = |before: Option<f64>, here: f64, after: Option<f64>| match (before, after)
+        {
+            (Some(before), Some(after)) => (after - before) / 2.0f64,
+            (Some(before), None) => here - before,
+            (None, Some(after)) => after - here,
+            (None, None) => 0.0f64,
+        };
+
+        self.cells()
+            .map(|(x, y, region)| match region {
+                TopographicRegion::Land(_) => 0.0f64,
+                TopographicRegion::Water(water) => {
+                    let here = water.depth().value();
+                    let dx = difference(
+                        depth_toward(x, y, AdjacencyDirection::Left),
+                        here,
+                        depth_toward(x, y, AdjacencyDirection::Right),
+                    );
+                    let dy = difference(
+                        depth_toward(x, y, AdjacencyDirection::Up),
+                        here,
+                        depth_toward(x, y, AdjacencyDirection::Down),
+                    );
+                    dx.hypot(dy)
+                }
+            })
+            .collect()
+    }
+
+    // 4-connected flood fill over the cells matching the predicate. Components
+    // come out in row-major order of their first cell, each sorted row-major.
+    fn connected_components<F: Fn(&TopographicRegion) -> bool>(
+        &self,
+        include: F,
+    ) -> Vec<Vec<(usize, usize)>> {
+        let mut visited = vec![false; self.data.len()];
+        let mut components = Vec::new();
+
+        for (x, y, region) in self.cells() {
+            let index = (y * self.width) + x;
+            if visited[index] || !include(region) {
+                continue;
+            }
+
+            visited[index] = true;
+            let mut component = Vec::new();
+            let mut stack = vec![(x, y)];
+
+            while let Some((cx, cy)) = stack.pop() {
+                component.push((cx, cy));
+
+                for (nx, ny) in self.orthogonal_neighbors(cx, cy) {
+                    let neighbor_index = (ny * self.width) + nx;
+                    if !visited[neighbor_index] && include(&self.data[neighbor_index]) {
+                        visited[neighbor_index] = true;
+                        stack.push((nx, ny));
+                    }
+                }
+            }
+
+            component.sort_by_key(|&(x, y)| (y, x));
+            components.push(component);
+        }
+
+        components
+    }
 
-                let adjacent_vegetation = if let Some(up) = up_adjacent {
-                    match up {
-                        TopographicRegion::Land(_) => false,
-                        TopographicRegion::Water(water) => water.has_vegetation_type(&veg_type),
+    pub fn water_bodies(&self) -> Vec<Vec<(usize, usize)>> {
+        self.connected_components(|region| !region.is_land())
+    }
+
+    pub fn land_masses(&self) -> Vec<Vec<(usize, usize)>> {
+        self.connected_components(|region| region.is_land())
+    }
+
+    /// Repeatedly joins two separate water bodies with a one cell wide channel
+    /// of shallow water along the shortest land crossing, as long as that
+    /// crossing is at most max_gap land cells. Returns how many channels were
+    /// carved.
+    pub fn carve_channels(&mut self, max_gap: usize) -> usize {
+        let mut carved = 0;
+
+        loop {
+            let bodies = self.water_bodies();
+            let mut body_of = vec![usize::MAX; self.data.len()];
+            for (body_index, body) in bodies.iter().enumerate() {
+                for &(x, y) in body {
+                    body_of[(y * self.width) + x] = body_index;
+                }
+            }
+
+            let Some(channel) = bodies.iter().enumerate().find_map(|(body_index, body)| {
+                self.channel_path(&body_of, body_index, body, max_gap)
+            }) else {
+                return carved;
+            };
+
+            for (x, y) in channel {
+                self.data[(y * self.width) + x] =
+                    TopographicRegion::Water(TopographicWaterRegion::new(
+                        BottomComposition::Mud,
+                        Vec::new(),
+                        None,
+                        Depth(CHANNEL_DEPTH),
+                    ));
+            }
+            carved += 1;
+        }
+    }
+
+    // Breadth-first search over land from one water body, returning the land
+    // cells of the shortest crossing to any other body
+    fn channel_path(
+        &self,
+        body_of: &[usize],
+        body_index: usize,
+        body: &[(usize, usize)],
+        max_gap: usize,
+    ) -> Option<Vec<(usize, usize)>> {
+        let mut distances = vec![usize::MAX; self.data.len()];
+        let mut parents: Vec<Option<(usize, usize)>> = vec![None; self.data.len()];
+        let mut queue = VecDeque::new();
+
+        for &(x, y) in body {
+            distances[(y * self.width) + x] = 0;
+            queue.push_back((x, y));
+        }
+
+        while let Some((x, y)) = queue.pop_front() {
+            let distance = distances[(y * self.width) + x];
+
+            for (nx, ny) in self.orthogonal_neighbors(x, y) {
+                let neighbor_index = (ny * self.width) + nx;
+                if distances[neighbor_index] != usize::MAX {
+                    continue;
+                }
+
+                if self.data[neighbor_index].is_land() {
+                    if distance < max_gap {
+                        distances[neighbor_index] = distance + 1;
+                        parents[neighbor_index] = Some((x, y));
+                        queue.push_back((nx, ny));
                     }
-                } else if let Some(left) = left_adjacent {
-                    match left {
-                        TopographicRegion::Land(_) => false,
-                        TopographicRegion::Water(water) => water.has_vegetation_type(&veg_type),
+                } else if body_of[neighbor_index] != body_index && distance > 0 {
+                    let mut path = Vec::new();
+                    let mut cell = Some((x, y));
+                    while let Some((cx, cy)) = cell {
+                        if !self.data[(cy * self.width) + cx].is_land() {
+                            break;
+                        }
+                        path.push((cx, cy));
+                        cell = parents[(cy * self.width) + cx];
                     }
-                } else {
-                    false
-                };
 
-                let vegetation_rate = depth
-                    .depth_range()
-                    .get_vegetation_rate(&veg_type, adjacent_vegetation);
+                    return Some(path);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Grows vegetation into bare neighboring cells in spring and summer and
+    /// kills patches back in fall and winter. Seeded by the map seed and
+    /// season, so the same map and season always change the same way.
+    pub fn apply_season(&mut self, season: Season) {
+        let season_index = Season::ALL
+            .iter()
+            .position(|x| *x == season)
+            .expect("Season must be present");
+        let mut rng = ChaCha8Rng::seed_from_u64(((self.seed as u64) << 8) | season_index as u64);
+
+        // Spreading reads the vegetation as it was before this season
+        let mut colonized = Vec::new();
+        for (x, y, region) in self.cells() {
+            let TopographicRegion::Water(water) = region else {
+                continue;
+            };
+            if !water.vegetation().is_empty() {
+                continue;
+            }
+
+            let sources: Vec<&VegetationPatch> = self
+                .orthogonal_neighbors(x, y)
+                .filter_map(|(nx, ny)| self.water_at(nx, ny))
+                .flat_map(|neighbor| neighbor.vegetation())
+                .filter(|patch| {
+                    !patch.vegetation.too_dark(water.depth(), water.clarity())
+                        && water
+                            .depth()
+                            .depth_range_in(&self.depth_ranges)
+                            .is_some_and(|range| {
+                                range.get_vegetation_rate(&patch.vegetation, true) > 0.0f64
+                            })
+                })
+                .collect();
+            if sources.is_empty() || !rng.random_bool(season.spread()) {
+                continue;
+            }
+
+            let source = sources[rng.random_range(0..sources.len())];
+            colonized.push((
+                (y * self.width) + x,
+                VegetationPatch {
+                    vegetation: source.vegetation,
+                    density: source.density / 2.0f32,
+                },
+            ));
+        }
+
+        for region in &mut self.data {
+            if let TopographicRegion::Water(water) = region {
+                water
+                    .vegetation
+                    .retain(|_| !rng.random_bool(season.dieback()));
+            }
+        }
+
+        for (index, patch) in colonized {
+            if let TopographicRegion::Water(water) = &mut self.data[index] {
+                water.vegetation.push(patch);
+            }
+        }
+    }
+
+    // Water cells matching the predicate with at least one orthogonal neighbor
+    // matching the edge predicate, in row-major order
+    fn edge_cells<F, G>(&self, cell: F, edge: G) -> Vec<(usize, usize)>
+    where
+        F: Fn(&TopographicWaterRegion) -> bool,
+        G: Fn(&TopographicRegion) -> bool,
+    {
+        self.cells()
+            .filter(|(_, _, region)| match region {
+                TopographicRegion::Land(_) => false,
+                TopographicRegion::Water(water) => cell(water),
+            })
+            .filter(|&(x, y, _)| {
+                self.orthogonal_neighbors(x, y)
+                    .any(|(nx, ny)| edge(&self.data[(ny * self.width) + nx]))
+            })
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    pub fn shoreline_cells(&self) -> Vec<(usize, usize)> {
+        self.edge_cells(|_| true, |neighbor| neighbor.is_land())
+    }
+
+    pub fn weedline_cells(&self) -> Vec<(usize, usize)> {
+        self.edge_cells(
+            |water| !water.vegetation().is_empty(),
+            |neighbor| match neighbor {
+                TopographicRegion::Land(_) => false,
+                TopographicRegion::Water(water) => water.vegetation().is_empty(),
+            },
+        )
+    }
+
+    /// Manhattan hop count from each cell to the nearest land cell, indexed
+    /// like the map data. Water with no land anywhere on the map reports
+    /// u32::MAX.
+    pub fn distance_to_shore(&self) -> Vec<u32> {
+        let mut distances = vec![u32::MAX; self.data.len()];
+        let mut queue = VecDeque::new();
+
+        for (x, y, region) in self.cells() {
+            if region.is_land() {
+                distances[(y * self.width) + x] = 0;
+                queue.push_back((x, y));
+            }
+        }
+
+        while let Some((x, y)) = queue.pop_front() {
+            let next_distance = distances[(y * self.width) + x] + 1;
 
-                let veg_random = rng.random_range(0..=100) as f64 / 100.0f64;
-                if veg_random <= vegetation_rate {
-                    vegetation = Some(veg_type)
+            for (nx, ny) in self.orthogonal_neighbors(x, y) {
+                let neighbor_index = (ny * self.width) + nx;
+                if distances[neighbor_index] == u32::MAX {
+                    distances[neighbor_index] = next_distance;
+                    queue.push_back((nx, ny));
                 }
+            }
+        }
 
-                let region = TopographicRegion::Water(TopographicWaterRegion::new(
-                    BottomComposition::Hard,
-                    vegetation,
-                    structure,
-                    depth,
-                ));
-                data.push(region);
+        distances
+    }
+
+    pub fn dropoffs(&self, min_gradient: f64) -> Vec<(usize, usize)> {
+        self.cells()
+            .filter_map(|(x, y, _)| {
+                let depth = self.water_at(x, y)?.depth().value();
+                self.orthogonal_neighbors(x, y)
+                    .filter_map(|(nx, ny)| self.water_at(nx, ny))
+                    .any(|neighbor| (neighbor.depth().value() - depth).abs() > min_gradient)
+                    .then_some((x, y))
+            })
+            .collect()
+    }
+
+    /// For each depth in intervals, the water cells shallower than it with a
+    /// water neighbor at or below it, in row-major order.
+    pub fn contours(&self, intervals: &[f64]) -> Vec<(f64, Vec<(usize, usize)>)> {
+        intervals
+            .iter()
+            .map(|&interval| {
+                let cells = self
+                    .cells()
+                    .filter_map(|(x, y, _)| {
+                        let depth = self.water_at(x, y)?.depth().value();
+                        (depth < interval
+                            && self
+                                .orthogonal_neighbors(x, y)
+                                .filter_map(|(nx, ny)| self.water_at(nx, ny))
+                                .any(|neighbor| neighbor.depth().value() >= interval))
+                        .then_some((x, y))
+                    })
+                    .collect();
+
+                (interval, cells)
+            })
+            .collect()
+    }
+
+    /// The top_n water cells by a weighted sum of HOTSPOT_WEEDLINE_WEIGHT for
+    /// a weedline cell, HOTSPOT_DROPOFF_WEIGHT for a dropoff steeper than
+    /// HOTSPOT_DROPOFF_GRADIENT and HOTSPOT_STRUCTURE_WEIGHT for structure in
+    /// or next to the cell. Cells scoring zero are left out and ties keep
+    /// row-major order.
+    pub fn fishing_hotspots(&self, top_n: usize) -> Vec<(usize, usize, f64)> {
+        let mut scores = vec![0.0f64; self.data.len()];
+        for (x, y) in self.weedline_cells() {
+            scores[(y * self.width) + x] += HOTSPOT_WEEDLINE_WEIGHT;
+        }
+        for (x, y) in self.dropoffs(HOTSPOT_DROPOFF_GRADIENT) {
+            scores[(y * self.width) + x] += HOTSPOT_DROPOFF_WEIGHT;
+        }
+
+        let mut hotspots: Vec<(usize, usize, f64)> = self
+            .cells()
+            .filter_map(|(x, y, _)| {
+                let water = self.water_at(x, y)?;
+                let near_structure = water.structure().is_some()
+                    || self
+                        .orthogonal_neighbors(x, y)
+                        .filter_map(|(nx, ny)| self.water_at(nx, ny))
+                        .any(|neighbor| neighbor.structure().is_some());
+
+                let mut score = scores[(y * self.width) + x];
+                if near_structure {
+                    score += HOTSPOT_STRUCTURE_WEIGHT;
+                }
+
+                (score > 0.0f64).then_some((x, y, score))
+            })
+            .collect();
+
+        hotspots.sort_by(|a, b| b.2.total_cmp(&a.2));
+        hotspots.truncate(top_n);
+        hotspots
+    }
+
+    pub fn habitat_summary(&self) -> HabitatSummary {
+        let waters: Vec<&TopographicWaterRegion> = self
+            .data
+            .iter()
+            .filter_map(|region| match region {
+                TopographicRegion::Land(_) => None,
+                TopographicRegion::Water(water) => Some(water),
+            })
+            .collect();
+
+        HabitatSummary {
+            land_cells: self.data.len() - waters.len(),
+            water_cells: waters.len(),
+            vegetation_counts: Vegetation::ALL
+                .iter()
+                .map(|vegetation| {
+                    let count = waters
+                        .iter()
+                        .filter(|water| water.has_vegetation_type(vegetation))
+                        .count();
+                    (*vegetation, count)
+                })
+                .collect(),
+            structure_counts: Structure::ALL
+                .iter()
+                .map(|structure| {
+                    let count = waters
+                        .iter()
+                        .filter(|water| water.has_structure_type(structure))
+                        .count();
+                    (*structure, count)
+                })
+                .collect(),
+        }
+    }
+
+    /// Fraction of all cells, land included, matching the predicate.
+    pub fn coverage<F: Fn(&TopographicRegion) -> bool>(&self, pred: F) -> f64 {
+        if self.data.is_empty() {
+            return 0.0f64;
+        }
+
+        self.data.iter().filter(|region| pred(region)).count() as f64 / self.data.len() as f64
+    }
+
+    /// Coordinates and depth of the deepest water cell. Ties go to the first
+    /// cell in row-major order.
+    pub fn deepest_cell(&self) -> Option<(usize, usize, f64)> {
+        self.extreme_water_cell(|depth, best| depth > best)
+    }
+
+    /// Coordinates and depth of the shallowest water cell. Ties go to the
+    /// first cell in row-major order.
+    pub fn shallowest_water_cell(&self) -> Option<(usize, usize, f64)> {
+        self.extreme_water_cell(|depth, best| depth < best)
+    }
+
+    // First water cell in row-major order that no later cell beats
+    fn extreme_water_cell<F>(&self, beats: F) -> Option<(usize, usize, f64)>
+    where
+        F: Fn(f64, f64) -> bool,
+    {
+        let mut best: Option<(usize, usize, f64)> = None;
+        for (x, y, region) in self.cells() {
+            let TopographicRegion::Water(water) = region else {
+                continue;
+            };
+
+            let depth = water.depth().value();
+            if best.is_none_or(|(_, _, best_depth)| beats(depth, best_depth)) {
+                best = Some((x, y, depth));
             }
         }
+
+        best
     }
 
-    data
-}
+    pub fn depth_stats(&self) -> DepthStats {
+        let mut depths: Vec<f64> = self
+            .data
+            .iter()
+            .filter_map(|region| match region {
+                TopographicRegion::Land(_) => None,
+                TopographicRegion::Water(water) => Some(water.depth().value()),
+            })
+            .collect();
+        depths.sort_by(|a, b| a.total_cmp(b));
+
+        let range_counts = self
+            .depth_ranges
+            .iter()
+            .map(|range| {
+                let count = depths
+                    .iter()
+                    .filter(|x| Depth(**x).depth_range_in(&self.depth_ranges) == Some(range))
+                    .count();
+                (range.name, count)
+            })
+            .collect();
+
+        if depths.is_empty() {
+            return DepthStats {
+                min: f64::NAN,
+                max: f64::NAN,
+                mean: f64::NAN,
+                median: f64::NAN,
+                range_counts,
+            };
+        }
+
+        let middle = depths.len() / 2;
+        let median = if depths.len().is_multiple_of(2) {
+            (depths[middle - 1] + depths[middle]) / 2.0f64
+        } else {
+            depths[middle]
+        };
+
+        DepthStats {
+            min: depths[0],
+            max: depths[depths.len() - 1],
+            mean: depths.iter().sum::<f64>() / depths.len() as f64,
+            median,
+            range_counts,
+        }
+    }
+
+    pub fn to_heightmap_png(&self, path: &Path) -> Result<(), ImageError> {
+        let mut image = RgbImage::new(self.width as u32, self.height as u32);
 
-impl Display for TopographicMap {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for y in 0..self.height {
             for x in 0..self.width {
-                let elem = self
-                    .data
-                    .get((y * self.width) + x)
-                    .expect("Indexed element must exist");
+                let pixel = self.shade_at(x, y).expect("Indexed element must exist");
 
-                write!(f, "{}", *elem)?;
+                image.put_pixel(x as u32, y as u32, pixel);
             }
+        }
 
-            writeln!(f)?;
+        image.save_with_format(path, ImageFormat::Png)
+    }
+
+    /// Aggregates each factor x factor block into one cell. A block is land when
+    /// most of its cells are, otherwise water at the block's average depth.
+    pub fn downsample(&self, factor: usize) -> TopographicMap {
+        let factor = factor.max(1);
+        let width = self.width.div_ceil(factor);
+        let height = self.height.div_ceil(factor);
+        let mut data = Vec::with_capacity(width * height);
+
+        for block_y in 0..height {
+            for block_x in 0..width {
+                let mut waters = Vec::new();
+                let mut elevations = Vec::new();
+
+                for y in (block_y * factor)..((block_y + 1) * factor).min(self.height) {
+                    for x in (block_x * factor)..((block_x + 1) * factor).min(self.width) {
+                        match self.get(x, y).expect("Indexed element must exist") {
+                            TopographicRegion::Land(land) => elevations.push(land.elevation()),
+                            TopographicRegion::Water(water) => waters.push(water),
+                        }
+                    }
+                }
+
+                if elevations.len() > waters.len() {
+                    let elevation = elevations.iter().sum::<f64>() / elevations.len() as f64;
+                    data.push(TopographicRegion::Land(TopographicLandRegion::new(
+                        elevation,
+                    )));
+                    continue;
+                }
+
+                let depth =
+                    waters.iter().map(|x| x.depth().value()).sum::<f64>() / waters.len() as f64;
+                let temperature =
+                    waters.iter().map(|x| x.temperature()).sum::<f64>() / waters.len() as f64;
+                let clarity = waters.iter().map(|x| x.clarity()).sum::<f64>() / waters.len() as f64;
+                let bottom = [
+                    BottomComposition::Mud,
+                    BottomComposition::Hard,
+                    BottomComposition::Gravel,
+                ]
+                .into_iter()
+                .max_by_key(|bottom| waters.iter().filter(|x| x.bottom() == bottom).count())
+                .expect("Bottom compositions must be present");
+
+                data.push(TopographicRegion::Water(
+                    TopographicWaterRegion::new(bottom, Vec::new(), None, Depth(depth))
+                        .with_temperature(temperature)
+                        .with_clarity(clarity),
+                ));
+            }
+        }
+
+        TopographicMap {
+            scale: (self.scale.0 * factor as f64, self.scale.1 * factor as f64),
+            ..self.with_data(width, height, data)
+        }
+    }
+
+    pub fn subregion(
+        &self,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+    ) -> Result<TopographicMap, MapError> {
+        if w == 0 || h == 0 {
+            return Err(MapError::ZeroDimensions);
+        }
+
+        let fits_width = x.checked_add(w).is_some_and(|right| right <= self.width);
+        let fits_height = y.checked_add(h).is_some_and(|bottom| bottom <= self.height);
+        if !fits_width || !fits_height {
+            return Err(MapError::OutOfBounds);
+        }
+
+        let data = (y..y + h)
+            .flat_map(|row| {
+                let start = (row * self.width) + x;
+                self.data[start..start + w].iter().cloned()
+            })
+            .collect();
+
+        Ok(TopographicMap {
+            // A cropped map no longer tiles
+            wrap: false,
+            ..self.with_data(w, h, data)
+        })
+    }
+
+    pub fn flip_horizontal(&self) -> TopographicMap {
+        let data = (0..self.height)
+            .flat_map(|y| (0..self.width).rev().map(move |x| (x, y)))
+            .map(|(x, y)| self.data[(y * self.width) + x].clone())
+            .collect();
+
+        TopographicMap {
+            current: (-self.current.0, self.current.1),
+            ..self.with_data(self.width, self.height, data)
+        }
+    }
+
+    pub fn rotate_cw(&self) -> TopographicMap {
+        let width = self.height;
+        let height = self.width;
+
+        // Row y of the rotated map is column y of the original, read bottom to top
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (y, self.height - 1 - x)))
+            .map(|(x, y)| self.data[(y * self.width) + x].clone())
+            .collect();
+
+        TopographicMap {
+            scale: (self.scale.1, self.scale.0),
+            current: (-self.current.1, self.current.0),
+            ..self.with_data(width, height, data)
+        }
+    }
+
+    // A map sharing this map's generation settings but holding different cells
+    fn with_data(
+        &self,
+        width: usize,
+        height: usize,
+        data: Vec<TopographicRegion>,
+    ) -> TopographicMap {
+        TopographicMap {
+            seed: self.seed,
+            width,
+            height,
+            scale: self.scale,
+            land_threshold: self.land_threshold,
+            wrap: self.wrap,
+            current: self.current,
+            depth_ranges: self.depth_ranges.clone(),
+            data,
+        }
+    }
+
+    pub fn to_depth_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let header: Vec<String> = (0..self.width).map(|x| x.to_string()).collect();
+        writeln!(writer, "{}", header.join(","))?;
+
+        for y in 0..self.height {
+            let row: Vec<String> = (0..self.width)
+                .map(
+                    |x| match self.get(x, y).expect("Indexed element must exist") {
+                        TopographicRegion::Land(_) => CSV_LAND_SENTINEL.to_string(),
+                        TopographicRegion::Water(water) => water.depth().value().to_string(),
+                    },
+                )
+                .collect();
+            writeln!(writer, "{}", row.join(","))?;
         }
 
         Ok(())
     }
+
+    /// A GeoJSON FeatureCollection with one Polygon per land mass, cell (x, y)
+    /// covering the unit square from (x, y) to (x + 1, y + 1). Land joined
+    /// across the edges of a wrapping map is split where it crosses an edge.
+    pub fn to_geojson(&self) -> String {
+        let unwrapped;
+        let map = if self.wrap {
+            unwrapped = TopographicMap {
+                wrap: false,
+                ..self.clone()
+            };
+            &unwrapped
+        } else {
+            self
+        };
+
+        let features: Vec<String> = map
+            .land_masses()
+            .iter()
+            .map(|cells| {
+                let rings: Vec<String> = component_rings(cells)
+                    .iter()
+                    .map(|ring| {
+                        let points: Vec<String> = ring
+                            .iter()
+                            .chain(ring.first())
+                            .map(|(x, y)| format!("[{},{}]", x, y))
+                            .collect();
+                        format!("[{}]", points.join(","))
+                    })
+                    .collect();
+
+                format!(
+                    "{{\"type\":\"Feature\",\"properties\":{{\"kind\":\"land\",\"cells\":{}}},\"geometry\":{{\"type\":\"Polygon\",\"coordinates\":[{}]}}}}",
+                    cells.len(),
+                    rings.join(",")
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"type\":\"FeatureCollection\",\"features\":[{}]}}",
+            features.join(",")
+        )
+    }
+
+    /// The plain glyphs inside a box-drawing border, with column indices
+    /// along the top and row indices down the side, both modulo 10.
+    pub fn to_framed_string(&self) -> String {
+        let mut text = String::with_capacity((self.width + 4) * (self.height + 3));
+
+        text.push_str("  ");
+        for x in 0..self.width {
+            text.push_str(&(x % 10).to_string());
+        }
+        text.push('\n');
+
+        text.push_str(" ┌");
+        text.push_str(&"─".repeat(self.width));
+        text.push_str("┐\n");
+
+        for y in 0..self.height {
+            text.push_str(&(y % 10).to_string());
+            text.push('│');
+            for x in 0..self.width {
+                let elem = self.get(x, y).expect("Indexed element must exist");
+                text.push_str(elem.symbol_in(&self.depth_ranges));
+            }
+            text.push_str("│\n");
+        }
+
+        text.push_str(" └");
+        text.push_str(&"─".repeat(self.width));
+        text.push_str("┘\n");
+
+        text
+    }
+
+    /// FNV-1a hash of the dimensions and every cell's contents. Stable across
+    /// platforms and releases, so it changes only when generated output does.
+    pub fn fingerprint(&self) -> u64 {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(self.width as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.height as u64).to_le_bytes());
+
+        for region in &self.data {
+            match region {
+                TopographicRegion::Land(land) => {
+                    bytes.push(0);
+                    bytes.extend_from_slice(&land.elevation().to_le_bytes());
+                }
+                TopographicRegion::Water(water) => {
+                    bytes.push(1);
+                    bytes.extend_from_slice(&water.depth().value().to_le_bytes());
+                    bytes.extend_from_slice(&water.temperature().to_le_bytes());
+                    bytes.extend_from_slice(&water.clarity().to_le_bytes());
+                    bytes.push(*water.bottom() as u8);
+                    bytes.push(water.vegetation().len() as u8);
+                    for patch in water.vegetation() {
+                        bytes.push(patch.vegetation as u8);
+                        bytes.extend_from_slice(&patch.density.to_le_bytes());
+                    }
+                    bytes.push(
+                        water
+                            .structure()
+                            .map_or(u8::MAX, |structure| *structure as u8),
+                    );
+                }
+            }
+        }
+
+        bytes.iter().fold(FNV64_OFFSET_BASIS, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(FNV64_PRIME)
+        })
+    }
+
+    pub fn to_plain_string(&self) -> String {
+        let mut text = String::with_capacity((self.width + 1) * self.height);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let elem = self.get(x, y).expect("Indexed element must exist");
+                text.push_str(elem.symbol_in(&self.depth_ranges));
+            }
+
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// Plain string downsampled by a single factor on both axes, keeping the
+    /// aspect ratio, so it fits within max_cols x max_rows characters. Empty
+    /// when either limit is zero.
+    pub fn to_fitted_string(&self, max_cols: usize, max_rows: usize) -> String {
+        if max_cols == 0 || max_rows == 0 {
+            return String::new();
+        }
+
+        let factor = self
+            .width
+            .div_ceil(max_cols)
+            .max(self.height.div_ceil(max_rows));
+        if factor <= 1 {
+            return self.to_plain_string();
+        }
+
+        self.downsample(factor).to_plain_string()
+    }
+
+    /// Like Display, but every cell is colored with a 24-bit RGB shade of its
+    /// exact depth, the same gradient as the heightmap, instead of one color
+    /// per depth range.
+    pub fn to_truecolor_string(&self) -> String {
+        let mut text = String::new();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let elem = self.get(x, y).expect("Indexed element must exist");
+                let Rgb([r, g, b]) = self.shade_at(x, y).expect("Indexed element must exist");
+                text.push_str(
+                    &elem
+                        .symbol_in(&self.depth_ranges)
+                        .truecolor(r, g, b)
+                        .to_string(),
+                );
+            }
+
+            text.push('\n');
+        }
+
+        text
+    }
+}
+
+/// Wall-clock time spent in each phase of map generation.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GenerationTimings {
+    /// Sampling the noise layers, including normalization.
+    pub noise: Duration,
+    /// Resolving depth, vegetation and structure cell by cell.
+    pub features: Duration,
+}
+
+impl GenerationTimings {
+    pub fn total(&self) -> Duration {
+        self.noise + self.features
+    }
+}
+
+/// Depth statistics over water cells. The float fields are NaN when the map
+/// has no water.
+#[derive(Debug, Clone)]
+pub struct DepthStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub range_counts: Vec<(DepthRangeName, usize)>,
+}
+
+/// Cell counts by habitat feature. A cell counts once for every vegetation
+/// type growing in it.
+#[derive(Debug, Clone)]
+pub struct HabitatSummary {
+    pub land_cells: usize,
+    pub water_cells: usize,
+    pub vegetation_counts: Vec<(Vegetation, usize)>,
+    pub structure_counts: Vec<(Structure, usize)>,
+}
+
+impl HabitatSummary {
+    /// Percent of the water cells, 0.0 when there is no water.
+    pub fn water_coverage(&self, count: usize) -> f64 {
+        if self.water_cells == 0 {
+            return 0.0f64;
+        }
+
+        count as f64 / self.water_cells as f64 * 100.0f64
+    }
+}
+
+impl Display for HabitatSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.land_cells + self.water_cells;
+        let percent = |count: usize| {
+            if total == 0 {
+                0.0f64
+            } else {
+                count as f64 / total as f64 * 100.0f64
+            }
+        };
+
+        writeln!(
+            f,
+            "Land: {} cells ({:.1}%)",
+            self.land_cells,
+            percent(self.land_cells)
+        )?;
+        writeln!(
+            f,
+            "Water: {} cells ({:.1}%)",
+            self.water_cells,
+            percent(self.water_cells)
+        )?;
+
+        writeln!(f, "Vegetation (% of water):")?;
+        for (vegetation, count) in &self.vegetation_counts {
+            writeln!(
+                f,
+                "  {}: {} ({:.1}%)",
+                vegetation.label(),
+                count,
+                self.water_coverage(*count)
+            )?;
+        }
+
+        writeln!(f, "Structure (% of water):")?;
+        for (structure, count) in &self.structure_counts {
+            writeln!(
+                f,
+                "  {}: {} ({:.1}%)",
+                structure.label(),
+                count,
+                self.water_coverage(*count)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TopographicMapBuilder {
+    seed: u32,
+    width: usize,
+    height: usize,
+    scale: (f64, f64),
+    land_threshold: f64,
+    depth_bounds: (f64, f64),
+    octaves: usize,
+    lacunarity: f64,
+    persistence: f64,
+    structure_noise_scale: Option<f64>,
+    thermocline_depth: f64,
+    clarity: f64,
+    current: (f64, f64),
+    wrap: bool,
+    normalize: bool,
+    depth_ranges: Vec<DepthRange>,
+}
+
+impl Default for TopographicMapBuilder {
+    fn default() -> Self {
+        Self {
+            seed: 42,
+            width: 96,
+            height: 64,
+            scale: (0.12f64, 0.12f64),
+            land_threshold: NOISE_LAND_MIN,
+            depth_bounds: (DEPTH_MIN, DEPTH_MAX),
+            octaves: 1,
+            lacunarity: 2.0f64,
+            persistence: 0.5f64,
+            structure_noise_scale: None,
+            thermocline_depth: DEFAULT_THERMOCLINE_DEPTH,
+            clarity: DEFAULT_CLARITY,
+            current: (0.0f64, 0.0f64),
+            wrap: false,
+            normalize: false,
+            depth_ranges: default_depth_ranges(),
+        }
+    }
+}
+
+impl TopographicMapBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn dimensions(mut self, width: usize, height: usize) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Sets the noise scale along both axes.
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.scale = (scale, scale);
+        self
+    }
+
+    /// Noise scale along x alone. Larger values make features narrower.
+    pub fn scale_x(mut self, scale_x: f64) -> Self {
+        self.scale.0 = scale_x;
+        self
+    }
+
+    /// Noise scale along y alone. Larger values make features shorter.
+    pub fn scale_y(mut self, scale_y: f64) -> Self {
+        self.scale.1 = scale_y;
+        self
+    }
+
+    /// Noise values below the threshold become land. Valid thresholds lie in
+    /// [-1.0, 1.0] and values outside it are clamped; the default is -0.5.
+    pub fn land_threshold(mut self, land_threshold: f64) -> Self {
+        self.land_threshold = land_threshold.clamp(NOISE_MIN, NOISE_MAX);
+        self
+    }
+
+    /// Depths water noise is mapped onto. The depth ranges only cover
+    /// DEPTH_MIN to DEPTH_MAX, so the bounds are clamped to that span.
+    pub fn depth_bounds(mut self, min: f64, max: f64) -> Self {
+        let min = min.clamp(DEPTH_MIN, DEPTH_MAX);
+        let max = max.clamp(min, DEPTH_MAX);
+        self.depth_bounds = (min, max);
+        self
+    }
+
+    /// Number of noise layers summed for the depth field. A single octave
+    /// keeps the smooth output of plain Perlin noise; 4 gives a rougher bottom.
+    pub fn octaves(mut self, octaves: usize) -> Self {
+        self.octaves = octaves.max(1);
+        self
+    }
+
+    /// Frequency multiplier applied between octaves.
+    pub fn lacunarity(mut self, lacunarity: f64) -> Self {
+        self.lacunarity = lacunarity;
+        self
+    }
+
+    /// Amplitude multiplier applied between octaves.
+    pub fn persistence(mut self, persistence: f64) -> Self {
+        self.persistence = persistence;
+        self
+    }
+
+    /// Places structures around Worley noise feature points sampled at this
+    /// scale instead of rolling every cell independently, so structure forms
+    /// clumps. Smaller scales spread the clumps further apart.
+    pub fn structure_noise_scale(mut self, structure_noise_scale: f64) -> Self {
+        self.structure_noise_scale = Some(structure_noise_scale);
+        self
+    }
+
+    /// Depth at which water turns from surface-warm to deep-cold.
+    pub fn thermocline_depth(mut self, thermocline_depth: f64) -> Self {
+        self.thermocline_depth = thermocline_depth;
+        self
+    }
+
+    /// Base water clarity from 0.0 (murky) to 1.0 (clear), clamped. Murkier
+    /// water keeps rooted vegetation out of deeper cells.
+    pub fn clarity(mut self, clarity: f64) -> Self {
+        self.clarity = clarity.clamp(0.0f64, 1.0f64);
+        self
+    }
+
+    /// Direction of a steady current in grid coordinates (x east, y south)
+    /// that drifts mats toward the downwind shore. Only the direction is
+    /// kept; (0.0, 0.0) means no current.
+    pub fn current(mut self, x: f64, y: f64) -> Self {
+        let length = x.hypot(y);
+        self.current = if length.is_finite() && length > 0.0f64 {
+            (x / length, y / length)
+        } else {
+            (0.0f64, 0.0f64)
+        };
+        self
+    }
+
+    /// Generates a tileable map whose left edge continues into its right edge
+    /// and top edge into its bottom. Neighbor lookups wrap around as well.
+    pub fn wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Replaces the depth range table that vegetation, structure and bottom
+    /// generation are drawn from. Ranges must be contiguous, may come in any
+    /// order, and must cover every depth from DEPTH_MIN to DEPTH_MAX; anything
+    /// else fails the build.
+    pub fn depth_ranges(mut self, mut depth_ranges: Vec<DepthRange>) -> Self {
+        depth_ranges.sort_by(|a, b| a.min.total_cmp(&b.min));
+        self.depth_ranges = depth_ranges;
+        self
+    }
+
+    /// Stretches the depth noise so its lowest and highest samples on this
+    /// map hit -1.0 and 1.0, filling every depth range instead of clustering
+    /// around mid depths. Off by default.
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    pub fn build(self) -> TopographicMap {
+        self.try_build().unwrap()
+    }
+
+    pub fn try_build(self) -> Result<TopographicMap, MapError> {
+        self.try_build_with(None)
+    }
+
+    /// Like try_build, but also reports how long each generation phase took.
+    /// Plain builds skip the clock entirely.
+    pub fn try_build_timed(self) -> Result<(TopographicMap, GenerationTimings), MapError> {
+        let mut timings = GenerationTimings::default();
+        let map = self.try_build_with(Some(&mut timings))?;
+        Ok((map, timings))
+    }
+
+    fn try_build_with(
+        self,
+        timings: Option<&mut GenerationTimings>,
+    ) -> Result<TopographicMap, MapError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(MapError::ZeroDimensions);
+        }
+
+        let valid_scale = |scale: f64| scale.is_finite() && scale > 0.0f64;
+        if !valid_scale(self.scale.0) || !valid_scale(self.scale.1) {
+            return Err(MapError::InvalidScale);
+        }
+
+        if self.width.checked_mul(self.height).is_none() {
+            return Err(MapError::CapacityOverflow);
+        }
+
+        if !valid_depth_ranges(&self.depth_ranges) {
+            return Err(MapError::InvalidDepthRanges);
+        }
+
+        let data = generate(&self, timings);
+        Ok(TopographicMap {
+            seed: self.seed,
+            width: self.width,
+            height: self.height,
+            scale: self.scale,
+            land_threshold: self.land_threshold,
+            wrap: self.wrap,
+            current: self.current,
+            depth_ranges: self.depth_ranges,
+            data,
+        })
+    }
+}
+
+fn default_depth_ranges() -> Vec<DepthRange> {
+    DEPTH_RANGES.to_vec()
+}
+
+// Sorted ranges with no gaps or overlaps spanning the whole depth scale
+fn valid_depth_ranges(ranges: &[DepthRange]) -> bool {
+    let (Some(first), Some(last)) = (ranges.first(), ranges.last()) else {
+        return false;
+    };
+
+    first.min <= DEPTH_MIN
+        && last.max >= DEPTH_MAX
+        && ranges.iter().all(|range| range.min < range.max)
+        && ranges.windows(2).all(|pair| pair[0].max == pair[1].min)
+}
+
+pub fn seed_from_str(name: &str) -> u32 {
+    name.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(FNV_PRIME)
+    })
+}
+
+#[derive(Debug, Clone, Copy)]
+enum AdjacencyDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+}
+
+impl AdjacencyDirection {
+    const ORTHOGONAL: [AdjacencyDirection; 4] = [
+        AdjacencyDirection::Up,
+        AdjacencyDirection::Down,
+        AdjacencyDirection::Left,
+        AdjacencyDirection::Right,
+    ];
+
+    // Neighbors already generated by the time a row-major scan reaches a cell
+    const PRECEDING: [AdjacencyDirection; 4] = [
+        AdjacencyDirection::UpLeft,
+        AdjacencyDirection::Up,
+        AdjacencyDirection::UpRight,
+        AdjacencyDirection::Left,
+    ];
+
+    fn apply(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        wrap: bool,
+    ) -> Option<(usize, usize)> {
+        let (dx, dy) = self.offset();
+
+        if wrap {
+            let wrapped_x = (x as isize + dx).rem_euclid(width as isize) as usize;
+            let wrapped_y = (y as isize + dy).rem_euclid(height as isize) as usize;
+            return Some((wrapped_x, wrapped_y));
+        }
+
+        let adjacent_x = x.checked_add_signed(dx)?;
+        let adjacent_y = y.checked_add_signed(dy)?;
+
+        (adjacent_x < width && adjacent_y < height).then_some((adjacent_x, adjacent_y))
+    }
+
+    fn offset(&self) -> (isize, isize) {
+        match self {
+            AdjacencyDirection::Up => (0, -1),
+            AdjacencyDirection::Down => (0, 1),
+            AdjacencyDirection::Left => (-1, 0),
+            AdjacencyDirection::Right => (1, 0),
+            AdjacencyDirection::UpLeft => (-1, -1),
+            AdjacencyDirection::UpRight => (1, -1),
+        }
+    }
+}
+
+fn get_adjacent(
+    map: &[TopographicRegion],
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+    direction: AdjacencyDirection,
+    wrap: bool,
+) -> Option<&TopographicRegion> {
+    let (adjacent_x, adjacent_y) = direction.apply(x, y, width, height, wrap)?;
+
+    // Cells not yet generated have no neighbor
+    map.get((adjacent_y * width) + adjacent_x)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SamplePoint {
+    Plane([f64; 2]),
+    Tiled([[f64; 2]; 4], [f64; 4]),
+}
+
+impl SamplePoint {
+    // When wrapping, the noise is blended with copies of itself shifted one
+    // map span left, up and diagonally, each copy weighted toward the edge it
+    // continues from, so opposite edges meet. Walking a torus through 4D
+    // Perlin noise would be simpler, but it jumps at lattice boundaries.
+    fn new(x: usize, y: usize, width: usize, height: usize, scale: (f64, f64), wrap: bool) -> Self {
+        let (scale_x, scale_y) = scale;
+        let [px, py] = [x as f64 * scale_x, y as f64 * scale_y];
+        if !wrap {
+            return SamplePoint::Plane([px, py]);
+        }
+
+        let span_x = width as f64 * scale_x;
+        let span_y = height as f64 * scale_y;
+        let u = x as f64 / width as f64;
+        let v = y as f64 / height as f64;
+
+        SamplePoint::Tiled(
+            [
+                [px, py],
+                [px - span_x, py],
+                [px, py - span_y],
+                [px - span_x, py - span_y],
+            ],
+            [
+                (1.0f64 - u) * (1.0f64 - v),
+                u * (1.0f64 - v),
+                (1.0f64 - u) * v,
+                u * v,
+            ],
+        )
+    }
+
+    fn sample<N: NoiseFn<f64, 2>>(&self, noise: &N, frequency: f64) -> f64 {
+        match self {
+            SamplePoint::Plane(point) => noise.get(point.map(|x| x * frequency)),
+            SamplePoint::Tiled(points, weights) => {
+                // Blending shrinks the spread of the noise toward the middle
+                // of the map, which the weights' norm undoes
+                let blended: f64 = points
+                    .iter()
+                    .zip(weights)
+                    .map(|(point, weight)| noise.get(point.map(|x| x * frequency)) * weight)
+                    .sum();
+                let norm = weights.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+                (blended / norm).clamp(NOISE_MIN, NOISE_MAX)
+            }
+        }
+    }
+}
+
+// Fractal Brownian motion, normalized by the total amplitude so the result
+// stays within the [-1, 1] range of a single Perlin sample
+fn fractal_noise(
+    perlin: &Perlin,
+    point: SamplePoint,
+    octaves: usize,
+    lacunarity: f64,
+    persistence: f64,
+) -> f64 {
+    let mut total = 0.0f64;
+    let mut frequency = 1.0f64;
+    let mut amplitude = 1.0f64;
+    let mut amplitude_sum = 0.0f64;
+
+    for _ in 0..octaves {
+        total += point.sample(perlin, frequency) * amplitude;
+        amplitude_sum += amplitude;
+        frequency *= lacunarity;
+        amplitude *= persistence;
+    }
+
+    total / amplitude_sum
+}
+
+// Noise layer values for a single cell. Sampling doesn't depend on any other
+// cell, which lets this pass run in parallel ahead of the adjacency pass.
+struct CellNoise {
+    depth: NoiseDepth,
+    bottom: f64,
+    temperature: f64,
+    clarity: f64,
+    structure_distance: Option<f64>,
+}
+
+fn structure_worley(config: &TopographicMapBuilder) -> Option<Worley> {
+    config.structure_noise_scale.map(|structure_scale| {
+        Worley::new(config.seed.wrapping_add(2))
+            .set_frequency(structure_scale)
+            .set_return_type(ReturnType::Distance)
+    })
+}
+
+fn sample_cell(
+    config: &TopographicMapBuilder,
+    perlin: &Perlin,
+    bottom_perlin: &Perlin,
+    temperature_perlin: &Perlin,
+    clarity_perlin: &Perlin,
+    structure_worley: Option<&Worley>,
+    index: usize,
+) -> CellNoise {
+    let x = index % config.width;
+    let y = index / config.width;
+    let point = SamplePoint::new(x, y, config.width, config.height, config.scale, config.wrap);
+
+    let depth = NoiseDepth(fractal_noise(
+        perlin,
+        point,
+        config.octaves,
+        config.lacunarity,
+        config.persistence,
+    ));
+
+    let structure_distance = structure_worley.map(|worley| {
+        SamplePoint::new(
+            x,
+            y,
+            config.width,
+            config.height,
+            (1.0f64, 1.0f64),
+            config.wrap,
+        )
+        .sample(worley, 1.0f64)
+    });
+
+    CellNoise {
+        depth,
+        bottom: point.sample(bottom_perlin, 1.0f64),
+        temperature: point.sample(temperature_perlin, 1.0f64),
+        clarity: point.sample(clarity_perlin, CLARITY_NOISE_FREQUENCY),
+        structure_distance,
+    }
+}
+
+fn sample_noise(config: &TopographicMapBuilder) -> Vec<CellNoise> {
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+
+        let perlin = Perlin::new(config.seed);
+        let bottom_perlin = Perlin::new(config.seed.wrapping_add(1));
+        let temperature_perlin = Perlin::new(config.seed.wrapping_add(3));
+        let clarity_perlin = Perlin::new(config.seed.wrapping_add(4));
+
+        // Worley isn't Sync, so each worker needs its own generator instead
+        // of sharing one
+        (0..(config.width * config.height))
+            .into_par_iter()
+            .map_init(
+                || structure_worley(config),
+                |worley, index| {
+                    sample_cell(
+                        config,
+                        &perlin,
+                        &bottom_perlin,
+                        &temperature_perlin,
+                        &clarity_perlin,
+                        worley.as_ref(),
+                        index,
+                    )
+                },
+            )
+            .collect()
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        sample_noise_serial(config)
+    }
+}
+
+// Parallel builds only sample serially to check against in tests
+#[cfg(any(test, not(feature = "parallel")))]
+fn sample_noise_serial(config: &TopographicMapBuilder) -> Vec<CellNoise> {
+    let perlin = Perlin::new(config.seed);
+    let bottom_perlin = Perlin::new(config.seed.wrapping_add(1));
+    let temperature_perlin = Perlin::new(config.seed.wrapping_add(3));
+    let clarity_perlin = Perlin::new(config.seed.wrapping_add(4));
+    let worley = structure_worley(config);
+
+    (0..(config.width * config.height))
+        .map(|index| {
+            sample_cell(
+                config,
+                &perlin,
+                &bottom_perlin,
+                &temperature_perlin,
+                &clarity_perlin,
+                worley.as_ref(),
+                index,
+            )
+        })
+        .collect()
+}
+
+// Linearly rescales depth noise onto the full noise range. A flat field has
+// nothing to stretch and is left alone.
+fn normalize_depth_noise(samples: &mut [CellNoise]) {
+    let (min, max) = samples
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), cell| {
+            (min.min(cell.depth.0), max.max(cell.depth.0))
+        });
+    if max <= min {
+        return;
+    }
+
+    for cell in samples {
+        cell.depth.0 = (cell.depth.0 - min) / (max - min) * (NOISE_MAX - NOISE_MIN) + NOISE_MIN;
+    }
+}
+
+// Boundary rings of a 4-connected set of cells, outer ring first and then
+// any holes. The outer ring runs counterclockwise and holes clockwise (with y
+// pointing up), listing only the corners where the boundary turns.
+fn component_rings(cells: &[(usize, usize)]) -> Vec<Vec<(usize, usize)>> {
+    let members: HashSet<(usize, usize)> = cells.iter().copied().collect();
+    let is_member = |x: Option<usize>, y: Option<usize>| match (x, y) {
+        (Some(x), Some(y)) => members.contains(&(x, y)),
+        _ => false,
+    };
+
+    // Each exposed cell side becomes an edge keeping the cell on its left
+    let mut edges = Vec::new();
+    for &(x, y) in cells {
+        let sides = [
+            (is_member(Some(x), y.checked_sub(1)), (x, y), (x + 1, y)),
+            (is_member(Some(x + 1), Some(y)), (x + 1, y), (x + 1, y + 1)),
+            (is_member(Some(x), Some(y + 1)), (x + 1, y + 1), (x, y + 1)),
+            (is_member(x.checked_sub(1), Some(y)), (x, y + 1), (x, y)),
+        ];
+        for (covered, from, to) in sides {
+            if !covered {
+                edges.push((from, to));
+            }
+        }
+    }
+
+    let mut outgoing: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+    for (index, (from, _)) in edges.iter().enumerate() {
+        outgoing.entry(*from).or_default().push(index);
+    }
+
+    let direction = |(from, to): ((usize, usize), (usize, usize))| {
+        (to.0 as i64 - from.0 as i64, to.1 as i64 - from.1 as i64)
+    };
+
+    // Where two cells touch only at a corner, turning right crosses over to
+    // the diagonal cell, so an enclosed pocket comes out as a hole touching
+    // the outer ring rather than a ring that touches itself
+    let successors: Vec<usize> = edges
+        .iter()
+        .map(|&edge| {
+            let heading = direction(edge);
+            outgoing[&edge.1]
+                .iter()
+                .copied()
+                .min_by_key(|&next| {
+                    let turn = direction(edges[next]);
+                    heading.0 * turn.1 - heading.1 * turn.0
+                })
+                .expect("Boundary edges must form rings")
+        })
+        .collect();
+
+    let mut visited = vec![false; edges.len()];
+    let mut rings = Vec::new();
+    for start in 0..edges.len() {
+        if visited[start] {
+            continue;
+        }
+
+        let mut ring = Vec::new();
+        let mut current = start;
+        loop {
+            visited[current] = true;
+            let next = successors[current];
+            if direction(edges[current]) != direction(edges[next]) {
+                ring.push(edges[current].1);
+            }
+
+            current = next;
+            if current == start {
+                break;
+            }
+        }
+
+        rings.push(ring);
+    }
+
+    // The outer ring encloses the largest signed area
+    let signed_area = |ring: &Vec<(usize, usize)>| {
+        ring.iter()
+            .zip(ring.iter().cycle().skip(1))
+            .map(|(a, b)| a.0 as i64 * b.1 as i64 - b.0 as i64 * a.1 as i64)
+            .sum::<i64>()
+    };
+    rings.sort_by_key(|ring| std::cmp::Reverse(signed_area(ring)));
+
+    rings
+}
+
+fn generate(
+    config: &TopographicMapBuilder,
+    timings: Option<&mut GenerationTimings>,
+) -> Vec<TopographicRegion> {
+    let TopographicMapBuilder {
+        seed,
+        width,
+        height,
+        land_threshold,
+        depth_bounds,
+        thermocline_depth,
+        clarity: base_clarity,
+        current,
+        wrap,
+        ..
+    } = *config;
+    let depth_ranges = &config.depth_ranges;
+
+    // The noise pass is independent per cell, but vegetation and structure
+    // depend on already generated neighbors and a single seeded stream, so
+    // they are resolved serially afterwards to keep output identical
+    let started = timings.as_ref().map(|_| Instant::now());
+    let mut samples = sample_noise(config);
+    if config.normalize {
+        normalize_depth_noise(&mut samples);
+    }
+    let sampled = timings.as_ref().map(|_| Instant::now());
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed.into());
+    let mut data = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+        for x in 0..width {
+            let cell = &samples[(y * width) + x];
+
+            if cell.depth.is_land_with(land_threshold) {
+                data.push(TopographicRegion::Land(TopographicLandRegion::from_noise(
+                    cell.depth,
+                    land_threshold,
+                )));
+            } else {
+                let depth = Depth::from_noise(cell.depth, land_threshold, depth_bounds);
+                let bottom = BottomComposition::from_depth(&depth, depth_ranges, cell.bottom);
+
+                let mut vegetation: Vec<VegetationPatch> = Vec::new();
+                let mut structure: Option<Structure> = None;
+
+                let preceding: Vec<&TopographicRegion> = AdjacencyDirection::PRECEDING
+                    .iter()
+                    .filter_map(|direction| {
+                        get_adjacent(&data, width, height, x, y, *direction, wrap)
+                    })
+                    .collect();
+
+                // Docks and drifting mats need to know where the bank is on every
+                // side, including neighbors not generated yet
+                let shore_directions: Vec<AdjacencyDirection> = AdjacencyDirection::ORTHOGONAL
+                    .iter()
+                    .filter(|direction| {
+                        direction
+                            .apply(x, y, width, height, wrap)
+                            .is_some_and(|(nx, ny)| {
+                                samples[(ny * width) + nx]
+                                    .depth
+                                    .is_land_with(land_threshold)
+                            })
+                    })
+                    .copied()
+                    .collect();
+                let on_shore = !shore_directions.is_empty();
+
+                // 1.0 with land straight downwind, -1.0 with it straight upwind
+                let downwind = shore_directions
+                    .iter()
+                    .map(|direction| {
+                        let (dx, dy) = direction.offset();
+                        dx as f64 * current.0 + dy as f64 * current.1
+                    })
+                    .max_by(|a, b| a.total_cmp(b))
+                    .unwrap_or(0.0f64);
+
+                let clarity =
+                    (base_clarity + cell.clarity * CLARITY_NOISE_AMPLITUDE).clamp(0.0f64, 1.0f64);
+
+                for veg_type in Vegetation::ALL {
+                    let adjacent_count = preceding
+                        .iter()
+                        .filter(|x| match x {
+                            TopographicRegion::Land(_) => false,
+                            TopographicRegion::Water(water) => water.has_vegetation_type(&veg_type),
+                        })
+                        .count();
+                    let adjacent_vegetation = adjacent_count > 0;
+
+                    let vegetation_rate = depth
+                        .depth_range_in(depth_ranges)
+                        .expect("Water depth must have a range")
+                        .get_vegetation_rate(&veg_type, adjacent_vegetation)
+                        * bottom.vegetation_factor(&veg_type);
+                    let vegetation_rate = if veg_type == Vegetation::Mats {
+                        (vegetation_rate * (1.0f64 + MAT_DRIFT_STRENGTH * downwind)).clamp(0.0, 1.0)
+                    } else {
+                        vegetation_rate
+                    };
+
+                    // Checked apart from the rate, since a roll of 0.0 passes even
+                    // a 0.0 rate
+                    let too_dark = veg_type.too_dark(&depth, clarity);
+                    let veg_random = rng.random_range(0..=100) as f64 / 100.0f64;
+                    if veg_random <= vegetation_rate && !too_dark {
+                        // Beds thin out toward their edges, where fewer neighbors share the type
+                        let neighbor_share =
+                            adjacent_count as f64 / AdjacencyDirection::PRECEDING.len() as f64;
+                        let density = ((vegetation_rate + neighbor_share) / 2.0f64).clamp(0.0, 1.0);
+
+                        vegetation.push(VegetationPatch {
+                            vegetation: veg_type,
+                            density: density as f32,
+                        });
+                    }
+                }
+
+                let struc_type = Structure::ALL[rng.random_range(0..Structure::ALL.len())];
+
+                // Wood washes up along the bank and docks are built from it, so a
+                // land neighbor clusters them like a neighboring structure of the
+                // same type would. Stumps are what is left of flooded timber.
+                let shore_structure = matches!(
+                    struc_type,
+                    Structure::Timber | Structure::Brush | Structure::Dock
+                );
+                let adjacent_structure = preceding.iter().any(|x| match x {
+                    TopographicRegion::Land(_) => shore_structure,
+                    TopographicRegion::Water(water) => {
+                        water.has_structure_type(&struc_type)
+                            || (struc_type == Structure::Stump
+                                && water.has_structure_type(&Structure::Timber))
+                    }
+                });
+
+                let depth_range = depth
+                    .depth_range_in(depth_ranges)
+                    .expect("Water depth must have a range");
+                let structure_rate = match cell.structure_distance {
+                    Some(distance) => {
+                        if distance < STRUCTURE_WORLEY_RADIUS {
+                            depth_range.get_structure_rate(&struc_type, true)
+                        } else {
+                            0.0f64
+                        }
+                    }
+                    None => depth_range.get_structure_rate(&struc_type, adjacent_structure),
+                };
+                let structure_rate = if struc_type == Structure::Dock && !on_shore {
+                    0.0f64
+                } else {
+                    structure_rate
+                };
+
+                let struc_random = rng.random_range(0..=100) as f64 / 100.0f64;
+                if struc_random < structure_rate {
+                    structure = Some(struc_type)
+                }
+
+                let temperature = depth.temperature(thermocline_depth)
+                    + cell.temperature * TEMPERATURE_NOISE_AMPLITUDE;
+                let region = TopographicRegion::Water(
+                    TopographicWaterRegion::new(bottom, vegetation, structure, depth)
+                        .with_temperature(temperature)
+                        .with_clarity(clarity),
+                );
+                data.push(region);
+            }
+        }
+    }
+
+    if let (Some(timings), Some(started), Some(sampled)) = (timings, started, sampled) {
+        timings.noise = sampled - started;
+        timings.features = sampled.elapsed();
+    }
+
+    data
+}
+
+impl Display for TopographicMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let elem = self
+                    .data
+                    .get((y * self.width) + x)
+                    .expect("Indexed element must exist");
+
+                match elem {
+                    TopographicRegion::Land(land) => write!(f, "{}", land)?,
+                    TopographicRegion::Water(water) => water.fmt_in(f, &self.depth_ranges)?,
+                }
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_map() -> TopographicMap {
+        TopographicMap::new(42, 96, 64, 0.12)
+    }
+
+    fn land() -> TopographicRegion {
+        TopographicRegion::Land(TopographicLandRegion::new(0.5f64))
+    }
+
+    fn water(depth: f64) -> TopographicRegion {
+        TopographicRegion::Water(TopographicWaterRegion::new(
+            BottomComposition::Mud,
+            Vec::new(),
+            None,
+            Depth(depth),
+        ))
+    }
+
+    fn grass(depth: f64) -> TopographicRegion {
+        TopographicRegion::Water(TopographicWaterRegion::new(
+            BottomComposition::Mud,
+            vec![VegetationPatch {
+                vegetation: Vegetation::Grass,
+                density: 1.0f32,
+            }],
+            None,
+            Depth(depth),
+        ))
+    }
+
+    // One string per row: '#' is land, a digit is bare water that deep and
+    // 'g' is grass in 2.0 of water
+    fn hand_map(rows: &[&str]) -> TopographicMap {
+        let data = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .map(|c| match c {
+                '#' => land(),
+                'g' => grass(2.0f64),
+                _ => water(c.to_digit(10).expect("Cell must be #, g or a digit") as f64),
+            })
+            .collect();
+
+        TopographicMap::new(42, 1, 1, 0.12f64).with_data(rows[0].len(), rows.len(), data)
+    }
+
+    #[test]
+    fn get_out_of_bounds_is_none() {
+        let map = default_map();
+
+        assert!(map.get(95, 63).is_some());
+        assert_eq!(map.get(96, 0), None);
+        assert_eq!(map.get(0, 64), None);
+        assert_eq!(map.get(usize::MAX, usize::MAX), None);
+    }
+
+    #[test]
+    fn get_known_regions() {
+        let map = default_map();
+
+        let Some(TopographicRegion::Water(water)) = map.get(10, 10) else {
+            panic!("(10, 10) must be water");
+        };
+        assert_eq!(water.depth().value(), 11.563880450717278);
+        assert_eq!(*water.bottom(), BottomComposition::Mud);
+
+        let Some(TopographicRegion::Land(land)) = map.get(11, 0) else {
+            panic!("(11, 0) must be land");
+        };
+        assert_eq!(land.elevation(), 0.0990969501379535);
+    }
+
+    #[test]
+    fn has_vegetation_type_only_matches_present_types() {
+        let water = TopographicWaterRegion::new(
+            BottomComposition::Mud,
+            vec![VegetationPatch {
+                vegetation: Vegetation::Grass,
+                density: 1.0f32,
+            }],
+            None,
+            Depth(2.0f64),
+        );
+
+        assert!(water.has_vegetation_type(&Vegetation::Grass));
+        assert!(!water.has_vegetation_type(&Vegetation::Reeds));
+    }
+
+    #[test]
+    fn depth_range_boundaries_belong_to_the_deeper_range() {
+        let cases = [
+            (0.0f64, DepthRangeName::SuperShallow),
+            (5.0f64, DepthRangeName::Shallow),
+            (7.0f64, DepthRangeName::MidDepth),
+            (10.0f64, DepthRangeName::Deep),
+            (15.0f64, DepthRangeName::Deep),
+        ];
+
+        for (depth, name) in cases {
+            let matching: Vec<DepthRangeName> = DEPTH_RANGES
+                .iter()
+                .filter(|range| range.contains(depth))
+                .map(|range| range.name)
+                .collect();
+            // Only the table closes its deepest range
+            let expected = if depth == DEPTH_MAX {
+                vec![]
+            } else {
+                vec![name]
+            };
+            assert_eq!(matching, expected, "depth {}", depth);
+            assert_eq!(
+                Depth(depth)
+                    .depth_range_in(&DEPTH_RANGES)
+                    .map(|range| range.name),
+                Some(name)
+            );
+        }
+
+        assert_eq!(Depth(-0.1f64).depth_range_in(&DEPTH_RANGES), None);
+        assert_eq!(Depth(15.1f64).depth_range_in(&DEPTH_RANGES), None);
+    }
+
+    #[test]
+    fn custom_tables_close_their_own_deepest_range() {
+        let mut ranges = DEPTH_RANGES.to_vec();
+        ranges.last_mut().unwrap().max = 20.0f64;
+
+        let deepest = Depth(20.0f64).depth_range_in(&ranges);
+        assert_eq!(deepest.map(|range| range.name), Some(DepthRangeName::Deep));
+        assert_eq!(Depth(20.1f64).depth_range_in(&ranges), None);
+        assert_eq!(Depth(DEPTH_MAX).depth_range_in(&DEPTH_RANGES[..3]), None);
+        assert_eq!(
+            Depth(10.0f64)
+                .depth_range_in(&DEPTH_RANGES[..3])
+                .map(|range| range.name),
+            Some(DepthRangeName::MidDepth)
+        );
+    }
+
+    #[test]
+    fn land_depth_formats_as_land() {
+        let depth = Depth::from(NoiseDepth(-0.9f64));
+
+        assert!(depth.is_land());
+        assert_eq!(depth.depth_range_in(&DEPTH_RANGES), None);
+        assert_eq!(
+            depth.symbol_in(&DEPTH_RANGES),
+            TopographicLandRegion::SYMBOL
+        );
+    }
+
+    #[test]
+    fn every_bottom_composition_appears() {
+        let map = default_map();
+
+        for bottom in [
+            BottomComposition::Mud,
+            BottomComposition::Hard,
+            BottomComposition::Gravel,
+        ] {
+            assert!(
+                map.cells()
+                    .any(|(x, y, _)| map.water_at(x, y).is_some_and(|w| *w.bottom() == bottom)),
+                "{} never appears",
+                bottom
+            );
+        }
+    }
+
+    #[test]
+    fn every_structure_type_appears() {
+        let summary = default_map().habitat_summary();
+
+        for (structure, count) in summary.structure_counts {
+            assert!(count > 0, "{} never appears", structure.label());
+        }
+    }
+
+    #[test]
+    fn vegetation_clusters_along_every_preceding_direction() {
+        let map = default_map();
+        let vegetated = |x: usize, y: usize| {
+            map.water_at(x, y)
+                .is_some_and(|water| !water.vegetation().is_empty())
+        };
+        let water_cells = map.cells().filter(|(_, _, r)| !r.is_land()).count();
+        let vegetated_cells = map.cells().filter(|&(x, y, _)| vegetated(x, y)).count();
+        let base_share = vegetated_cells as f64 / water_cells as f64;
+
+        // Every direction the generator looks back along should show beds
+        // clumping well above the map-wide share, not just up and left
+        let shares: Vec<f64> = AdjacencyDirection::PRECEDING
+            .iter()
+            .map(|direction| {
+                let (mut pairs, mut shared) = (0, 0);
+                for (x, y, _) in map.cells() {
+                    let Some((nx, ny)) = direction.apply(x, y, map.width(), map.height(), false)
+                    else {
+                        continue;
+                    };
+                    if vegetated(x, y) && map.water_at(nx, ny).is_some() {
+                        pairs += 1;
+                        if vegetated(nx, ny) {
+                            shared += 1;
+                        }
+                    }
+                }
+                shared as f64 / pairs as f64
+            })
+            .collect();
+        for share in &shares {
+            assert!(*share > base_share, "{} <= {}", share, base_share);
+        }
+
+        // and with no one direction dominating the shape of the beds
+        let (min, max) = shares
+            .iter()
+            .fold((f64::INFINITY, 0.0f64), |(min, max), x| {
+                (min.min(*x), max.max(*x))
+            });
+        assert!(max / min < 1.1f64, "{:?}", shares);
+    }
+
+    #[test]
+    fn heightmap_png_matches_map() {
+        let map = TopographicMap::new(42, 12, 8, 0.12f64);
+        let path = std::env::temp_dir().join(format!("heightmap-{}.png", std::process::id()));
+
+        map.to_heightmap_png(&path).unwrap();
+        let image = image::open(&path).unwrap().to_rgb8();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(image.dimensions(), (12, 8));
+        for (x, y, region) in map.cells() {
+            let expected = match region {
+                TopographicRegion::Land(_) => Rgb(HEIGHTMAP_LAND),
+                TopographicRegion::Water(water) => water.depth().heightmap_pixel(),
+            };
+            assert_eq!(*image.get_pixel(x as u32, y as u32), expected);
+        }
+
+        assert_eq!(Depth(DEPTH_MIN).heightmap_pixel(), Rgb(HEIGHTMAP_SHALLOW));
+        assert_eq!(Depth(DEPTH_MAX).heightmap_pixel(), Rgb(HEIGHTMAP_DEEP));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip_preserves_map() {
+        let map = default_map();
+
+        let json = serde_json::to_string(&map).unwrap();
+        let loaded: TopographicMap = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(loaded, map);
+        assert_eq!(loaded.to_string(), map.to_string());
+    }
+
+    #[test]
+    fn plain_string_has_no_escape_codes() {
+        let map = default_map();
+        let text = map.to_plain_string();
+
+        assert!(!text.contains('\x1b'));
+        let rows: Vec<&str> = text.lines().collect();
+        assert_eq!(rows.len(), map.height());
+        for row in rows {
+            assert_eq!(row.chars().count(), map.width());
+        }
+    }
+
+    fn land_cells(map: &TopographicMap) -> usize {
+        map.cells()
+            .filter(|(_, _, region)| region.is_land())
+            .count()
+    }
+
+    #[test]
+    fn builder_defaults_match_new_and_threshold_adds_land() {
+        let default_build = TopographicMapBuilder::new().build();
+        assert_eq!(default_build, default_map());
+
+        let higher = TopographicMap::builder().land_threshold(0.0f64).build();
+        assert!(land_cells(&higher) > land_cells(&default_build));
+    }
+
+    #[test]
+    fn land_rises_strictly_with_threshold() {
+        let counts: Vec<usize> = [-0.8f64, -0.5f64, -0.2f64, 0.1f64, 0.4f64]
+            .iter()
+            .map(|threshold| {
+                land_cells(&TopographicMap::builder().land_threshold(*threshold).build())
+            })
+            .collect();
+
+        assert!(
+            counts.windows(2).all(|pair| pair[0] < pair[1]),
+            "{:?}",
+            counts
+        );
+        assert!(!NoiseDepth(-0.5f64).is_land_with(-0.5f64));
+        assert!(NoiseDepth(-0.5f64).is_land_with(-0.4f64));
+    }
+
+    // Places along a row where the bottom turns from deepening to shallowing
+    // or back, counting only runs of three water cells
+    fn slope_reversals(map: &TopographicMap) -> usize {
+        let depth_at = |x: usize, y: usize| map.water_at(x, y).map(|water| water.depth().value());
+
+        (0..map.height())
+            .flat_map(|y| (1..map.width() - 1).map(move |x| (x, y)))
+            .filter(
+                |&(x, y)| match (depth_at(x - 1, y), depth_at(x, y), depth_at(x + 1, y)) {
+                    (Some(left), Some(here), Some(right)) => {
+                        (here - left) * (right - here) < 0.0f64
+                    }
+                    _ => false,
+                },
+            )
+            .count()
+    }
+
+    #[test]
+    fn more_octaves_roughen_the_bottom() {
+        let smooth = TopographicMap::builder().octaves(1).build();
+        let rough = TopographicMap::builder().octaves(4).build();
+
+        assert!(slope_reversals(&rough) > slope_reversals(&smooth));
+    }
+
+    #[test]
+    fn try_build_reports_each_error() {
+        assert_eq!(
+            TopographicMap::try_new(42, 0, 64, 0.12f64),
+            Err(MapError::ZeroDimensions)
+        );
+        assert_eq!(
+            TopographicMap::try_new(42, 96, 0, 0.12f64),
+            Err(MapError::ZeroDimensions)
+        );
+        for scale in [0.0f64, -0.12f64, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                TopographicMap::try_new(42, 96, 64, scale),
+                Err(MapError::InvalidScale)
+            );
+        }
+        assert_eq!(
+            TopographicMap::try_new(42, usize::MAX, 2, 0.12f64),
+            Err(MapError::CapacityOverflow)
+        );
+        assert_eq!(
+            default_map().subregion(90, 0, 10, 10),
+            Err(MapError::OutOfBounds)
+        );
+        assert_eq!(
+            TopographicMap::builder()
+                .depth_ranges(DEPTH_RANGES[1..].to_vec())
+                .try_build(),
+            Err(MapError::InvalidDepthRanges)
+        );
+    }
+
+    #[test]
+    fn cells_yields_every_cell_in_row_major_order() {
+        let map = default_map();
+
+        assert_eq!(map.cells().count(), map.width() * map.height());
+        for i in [
+            0,
+            1,
+            map.width() - 1,
+            map.width(),
+            1000,
+            map.width() * map.height() - 1,
+        ] {
+            let (x, y, region) = map.cells().nth(i).unwrap();
+            assert_eq!((x, y), (i % map.width(), i / map.width()));
+            assert_eq!(Some(region), map.get(x, y));
+        }
+    }
+
+    #[test]
+    fn water_bodies_finds_separate_pools() {
+        let map = hand_map(&["11#", "1##", "##2", "#22"]);
+
+        assert_eq!(
+            map.water_bodies(),
+            vec![vec![(0, 0), (1, 0), (0, 1)], vec![(2, 2), (1, 3), (2, 3)]]
+        );
+        assert_eq!(map.land_masses().len(), 1);
+    }
+
+    #[test]
+    fn shoreline_and_weedline_cells() {
+        let map = hand_map(&["#11", "gg1", "g11"]);
+
+        assert_eq!(map.shoreline_cells(), vec![(1, 0), (0, 1)]);
+        assert_eq!(map.weedline_cells(), vec![(1, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn depth_stats_counts_every_water_cell() {
+        let map = default_map();
+        let stats = map.depth_stats();
+        let water_cells = map.cells().filter(|(_, _, r)| !r.is_land()).count();
+
+        assert_eq!(
+            stats
+                .range_counts
+                .iter()
+                .map(|(_, count)| count)
+                .sum::<usize>(),
+            water_cells
+        );
+        assert!(stats.min <= stats.mean && stats.mean <= stats.max);
+        assert!(stats.min <= stats.median && stats.median <= stats.max);
+
+        let pools = hand_map(&["13", "#8"]).depth_stats();
+        assert_eq!(
+            (pools.min, pools.max, pools.mean, pools.median),
+            (1.0, 8.0, 4.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn depth_csv_parses_back_to_the_map() {
+        let map = default_map();
+        let mut csv = Vec::new();
+        map.to_depth_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+
+        let mut lines = csv.lines();
+        let header: Vec<usize> = lines
+            .next()
+            .unwrap()
+            .split(',')
+            .map(|x| x.parse().unwrap())
+            .collect();
+        assert_eq!(header, (0..map.width()).collect::<Vec<usize>>());
+
+        let rows: Vec<&str> = lines.collect();
+        assert_eq!(rows.len(), map.height());
+        for (y, row) in rows.iter().enumerate() {
+            let values: Vec<&str> = row.split(',').collect();
+            assert_eq!(values.len(), map.width());
+            for (x, value) in values.iter().enumerate() {
+                match map.water_at(x, y) {
+                    Some(water) => assert_eq!(value.parse::<f64>().unwrap(), water.depth().value()),
+                    None => assert_eq!(*value, CSV_LAND_SENTINEL),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn distance_to_shore_counts_hops() {
+        let map = hand_map(&["#111", "1111", "1111"]);
+
+        assert_eq!(
+            map.distance_to_shore(),
+            vec![0, 1, 2, 3, 1, 2, 3, 4, 2, 3, 4, 5]
+        );
+        assert!(
+            hand_map(&["11", "11"])
+                .distance_to_shore()
+                .iter()
+                .all(|x| *x == u32::MAX)
+        );
+    }
+
+    #[test]
+    fn dropoffs_find_ledges_but_not_flats() {
+        let ledge = hand_map(&["1199", "1199"]);
+        assert_eq!(ledge.dropoffs(2.0f64), vec![(1, 0), (2, 0), (1, 1), (2, 1)]);
+        assert!(ledge.dropoffs(8.0f64).is_empty());
+
+        let flat = hand_map(&["3334", "3#34"]);
+        assert!(flat.dropoffs(2.0f64).is_empty());
+    }
+
+    #[test]
+    fn describe_lists_vegetation_and_structure() {
+        let water = TopographicWaterRegion::new(
+            BottomComposition::Gravel,
+            vec![VegetationPatch {
+                vegetation: Vegetation::Grass,
+                density: 0.5f32,
+            }],
+            Some(Structure::Dock),
+            Depth(2.0f64),
+        );
+
+        assert_eq!(
+            water.describe_in(&DEPTH_RANGES),
+            "Gravel bottom, depth 2.00 (super-shallow), vegetation: grass, structure: dock"
+        );
+    }
+
+    #[test]
+    fn cell_can_hold_two_vegetation_types() {
+        let patch = |vegetation| VegetationPatch {
+            vegetation,
+            density: 0.5f32,
+        };
+        let water = TopographicWaterRegion::new(
+            BottomComposition::Mud,
+            vec![patch(Vegetation::Grass), patch(Vegetation::Reeds)],
+            None,
+            Depth(2.0f64),
+        );
+
+        assert!(water.has_vegetation_type(&Vegetation::Grass));
+        assert!(water.has_vegetation_type(&Vegetation::Reeds));
+        assert_eq!(water.symbol_in(&DEPTH_RANGES), Vegetation::Grass.symbol());
+        assert!(
+            water
+                .describe_in(&DEPTH_RANGES)
+                .contains("vegetation: grass and reeds")
+        );
+
+        let map = default_map();
+        assert!(
+            map.cells()
+                .any(|(x, y, _)| map.water_at(x, y).is_some_and(|w| w.vegetation().len() > 1))
+        );
+    }
+
+    // Mean distance from each structured cell to the nearest other one
+    fn mean_nearest_structure_distance(map: &TopographicMap) -> f64 {
+        let structured: Vec<(f64, f64)> = map
+            .cells()
+            .filter(|&(x, y, _)| map.water_at(x, y).is_some_and(|w| w.structure().is_some()))
+            .map(|(x, y, _)| (x as f64, y as f64))
+            .collect();
+        assert!(structured.len() > 1);
+
+        structured
+            .iter()
+            .enumerate()
+            .map(|(i, &(x, y))| {
+                structured
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(_, &(ox, oy))| (ox - x).hypot(oy - y))
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .sum::<f64>()
+            / structured.len() as f64
+    }
+
+    #[test]
+    fn worley_structures_cluster_tighter_than_independent_rolls() {
+        let independent = TopographicMap::builder().seed(7).build();
+        let worley = TopographicMap::builder()
+            .seed(7)
+            .structure_noise_scale(0.1f64)
+            .build();
+
+        let independent_distance = mean_nearest_structure_distance(&independent);
+        let worley_distance = mean_nearest_structure_distance(&worley);
+        assert!(
+            worley_distance < independent_distance,
+            "{} vs {}",
+            worley_distance,
+            independent_distance
+        );
+    }
+
+    // Mean depth noise step between the cells paired by index
+    fn mean_step(samples: &[CellNoise], pairs: &[(usize, usize)]) -> f64 {
+        pairs
+            .iter()
+            .map(|&(a, b)| (samples[a].depth.0 - samples[b].depth.0).abs())
+            .sum::<f64>()
+            / pairs.len() as f64
+    }
+
+    #[test]
+    fn wrapping_edges_are_continuous() {
+        let config = TopographicMap::builder().wrap(true);
+        let samples = sample_noise(&config);
+        let (width, height) = (config.width, config.height);
+        let index = |x: usize, y: usize| (y * width) + x;
+
+        let across_seam: Vec<(usize, usize)> = (0..height)
+            .map(|y| (index(width - 1, y), index(0, y)))
+            .collect();
+        let across_columns: Vec<(usize, usize)> = (0..height)
+            .flat_map(|y| (1..width).map(move |x| (index(x - 1, y), index(x, y))))
+            .collect();
+        let seam = mean_step(&samples, &across_seam);
+        let interior = mean_step(&samples, &across_columns);
+        assert!(seam < interior * 1.5f64, "{} vs {}", seam, interior);
+
+        let across_seam: Vec<(usize, usize)> = (0..width)
+            .map(|x| (index(x, height - 1), index(x, 0)))
+            .collect();
+        let across_rows: Vec<(usize, usize)> = (1..height)
+            .flat_map(|y| (0..width).map(move |x| (index(x, y - 1), index(x, y))))
+            .collect();
+        let seam = mean_step(&samples, &across_seam);
+        let interior = mean_step(&samples, &across_rows);
+        assert!(seam < interior * 1.5f64, "{} vs {}", seam, interior);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_sampling_matches_serial() {
+        for seed in [0, 7, 42, 1234] {
+            let config = TopographicMap::builder()
+                .seed(seed)
+                .octaves(3)
+                .structure_noise_scale(0.1f64);
+            let parallel = sample_noise(&config);
+            let serial = sample_noise_serial(&config);
+
+            assert_eq!(parallel.len(), serial.len());
+            for (a, b) in parallel.iter().zip(&serial) {
+                assert_eq!(a.depth.0, b.depth.0);
+                assert_eq!(a.bottom, b.bottom);
+                assert_eq!(a.temperature, b.temperature);
+                assert_eq!(a.clarity, b.clarity);
+                assert_eq!(a.structure_distance, b.structure_distance);
+            }
+        }
+    }
+
+    #[test]
+    fn downsample_halves_dimensions_and_merges_blocks() {
+        let small = default_map().downsample(2);
+        assert_eq!((small.width(), small.height()), (48, 32));
+
+        let map = hand_map(&["##13", "##35"]).downsample(2);
+        assert_eq!((map.width(), map.height()), (2, 1));
+        assert_eq!(map.elevation_at(0, 0), Some(0.5f64));
+        assert_eq!(map.water_at(1, 0).unwrap().depth().value(), 3.0f64);
+    }
+
+    #[test]
+    fn subregion_crops_cells() {
+        let map = hand_map(&["123", "45#", "789"]);
+        let crop = map.subregion(1, 1, 2, 2).unwrap();
+
+        assert_eq!((crop.width(), crop.height()), (2, 2));
+        assert_eq!(
+            crop.to_plain_string(),
+            hand_map(&["5#", "89"]).to_plain_string()
+        );
+        assert_eq!(crop.get(0, 0), map.get(1, 1));
+        assert_eq!(crop.get(1, 1), map.get(2, 2));
+        assert_eq!(map.subregion(2, 2, 2, 1), Err(MapError::OutOfBounds));
+        assert_eq!(map.subregion(0, 0, 0, 1), Err(MapError::ZeroDimensions));
+    }
+
+    #[test]
+    fn flips_and_rotations_round_trip() {
+        let map = TopographicMap::builder()
+            .dimensions(12, 8)
+            .current(1.0f64, 0.5f64)
+            .build();
+
+        assert_eq!(map.flip_horizontal().flip_horizontal(), map);
+
+        let rotated = map.rotate_cw();
+        assert_eq!((rotated.width(), rotated.height()), (8, 12));
+        for (x, y, region) in rotated.cells() {
+            assert_eq!(Some(region), map.get(y, map.height() - 1 - x));
+        }
+        assert_eq!(rotated.rotate_cw().rotate_cw().rotate_cw(), map);
+    }
+
+    #[test]
+    fn seed_names_are_stable() {
+        let a = TopographicMap::from_seed_str("Lake Travis", 32, 24, 0.12f64);
+        let b = TopographicMap::from_seed_str("Lake Travis", 32, 24, 0.12f64);
+        let c = TopographicMap::from_seed_str("Lake Austin", 32, 24, 0.12f64);
+
+        assert_eq!(a, b);
+        assert_ne!(a.seed(), c.seed());
+        assert_ne!(a, c);
+        // 32-bit FNV-1a reference values
+        assert_eq!(seed_from_str(""), 0x811c9dc5);
+        assert_eq!(seed_from_str("a"), 0xe40c292c);
+    }
+
+    #[test]
+    fn interior_beds_are_denser_than_edges() {
+        let map = default_map();
+        let vegetated = |x: usize, y: usize| map.vegetation_density_at(x, y) > 0.0f32;
+        let mean = |cells: &[(usize, usize)]| {
+            cells
+                .iter()
+                .map(|&(x, y)| map.vegetation_density_at(x, y) as f64)
+                .sum::<f64>()
+                / cells.len() as f64
+        };
+
+        let (interior, edge): (Vec<_>, Vec<_>) = map
+            .cells()
+            .filter(|&(x, y, _)| vegetated(x, y))
+            .map(|(x, y, _)| (x, y))
+            .partition(|&(x, y)| {
+                map.orthogonal_neighbors(x, y)
+                    .all(|(nx, ny)| vegetated(nx, ny))
+            });
+
+        assert!(!interior.is_empty() && !edge.is_empty());
+        assert!(
+            mean(&interior) > mean(&edge),
+            "{} <= {}",
+            mean(&interior),
+            mean(&edge)
+        );
+    }
+
+    #[test]
+    fn legend_lists_every_variant() {
+        let legend = TopographicMap::legend();
+
+        for name in DepthRangeName::ALL {
+            assert!(legend.contains(&format!("{} = {} water", name, name.label())));
+        }
+        for vegetation in Vegetation::ALL {
+            assert!(legend.contains(&format!("{} = {}", vegetation, vegetation.label())));
+        }
+        for structure in Structure::ALL {
+            assert!(legend.contains(&format!("{} = {}", structure, structure.label())));
+        }
+    }
+
+    #[test]
+    fn hard_bottoms_suppress_rooted_vegetation() {
+        for vegetation in [
+            Vegetation::Grass,
+            Vegetation::Reeds,
+            Vegetation::Lilypads,
+            Vegetation::Hydrilla,
+        ] {
+            assert!(
+                BottomComposition::Hard.vegetation_factor(&vegetation)
+                    < BottomComposition::Mud.vegetation_factor(&vegetation)
+            );
+        }
+
+        // Compared within one depth range so depth doesn't decide the outcome
+        for range in [DepthRangeName::Shallow, DepthRangeName::MidDepth] {
+            let share = |bottom: BottomComposition| {
+                let (mut rooted, mut total) = (0, 0);
+                for seed in 1..6 {
+                    let map = TopographicMap::new(seed, 96, 64, 0.12f64);
+                    for (x, y, _) in map.cells() {
+                        let Some(water) = map.water_at(x, y) else {
+                            continue;
+                        };
+                        if map.range_name_at(x, y) == Some(range) && *water.bottom() == bottom {
+                            total += 1;
+                            if water.vegetation().iter().any(|p| p.vegetation.is_rooted()) {
+                                rooted += 1;
+                            }
+                        }
+                    }
+                }
+                rooted as f64 / total as f64
+            };
+
+            let (hard, mud) = (
+                share(BottomComposition::Hard),
+                share(BottomComposition::Mud),
+            );
+            assert!(hard < mud, "{:?}: {} >= {}", range, hard, mud);
+        }
+    }
+
+    #[test]
+    fn ledge_weed_and_timber_cell_is_the_top_hotspot() {
+        let mut map = hand_map(&["22222", "22222", "22g99", "22222"]);
+        map.data[(2 * 5) + 2] = TopographicRegion::Water(TopographicWaterRegion::new(
+            BottomComposition::Mud,
+            vec![VegetationPatch {
+                vegetation: Vegetation::Grass,
+                density: 1.0f32,
+            }],
+            Some(Structure::Timber),
+            Depth(2.0f64),
+        ));
+
+        let hotspots = map.fishing_hotspots(3);
+
+        assert_eq!(hotspots.len(), 3);
+        assert_eq!(
+            hotspots[0],
+            (
+                2,
+                2,
+                HOTSPOT_WEEDLINE_WEIGHT + HOTSPOT_DROPOFF_WEIGHT + HOTSPOT_STRUCTURE_WEIGHT
+            )
+        );
+        assert!(hotspots[1].2 < hotspots[0].2);
+        assert!(map.fishing_hotspots(0).is_empty());
+    }
+
+    #[test]
+    fn geojson_has_one_polygon_per_land_mass() {
+        let map = default_map();
+        let geojson: serde_json::Value =
+            serde_json::from_str(&map.to_geojson()).expect("GeoJSON must parse");
+
+        assert_eq!(geojson["type"], "FeatureCollection");
+        let features = geojson["features"]
+            .as_array()
+            .expect("Features must be an array");
+        assert_eq!(features.len(), map.land_masses().len());
+        assert!(features.len() > 1);
+
+        let map = hand_map(&["#2222", "22222", "22###", "22#2#", "22###"]);
+        let geojson: serde_json::Value =
+            serde_json::from_str(&map.to_geojson()).expect("GeoJSON must parse");
+        let features = geojson["features"]
+            .as_array()
+            .expect("Features must be an array");
+        assert_eq!(features.len(), 2);
+
+        let rings = |index: usize| -> Vec<serde_json::Value> {
+            features[index]["geometry"]["coordinates"]
+                .as_array()
+                .expect("Coordinates must be an array")
+                .clone()
+        };
+        let islet = features
+            .iter()
+            .position(|feature| feature["properties"]["cells"] == 1)
+            .expect("Single cell island must be present");
+        let atoll = 1 - islet;
+
+        assert_eq!(features[atoll]["properties"]["cells"], 8);
+        assert_eq!(rings(islet).len(), 1);
+        assert_eq!(rings(islet)[0].as_array().map(Vec::len), Some(5));
+        // The lagoon is a hole in the atoll
+        assert_eq!(rings(atoll).len(), 2);
+        for ring in rings(atoll) {
+            let points = ring.as_array().expect("Ring must be an array");
+            assert_eq!(points.first(), points.last());
+        }
+    }
+
+    #[test]
+    fn radial_gradient_gives_concentric_contours() {
+        let distance = |x: usize, y: usize| (x as f64 - 10.0f64).hypot(y as f64 - 10.0f64);
+        let data = (0..21 * 21)
+            .map(|index| water(distance(index % 21, index / 21)))
+            .collect();
+        let map = TopographicMap::new(42, 1, 1, 0.12f64).with_data(21, 21, data);
+
+        let contours = map.contours(&[3.0f64, 7.0f64]);
+
+        assert_eq!(contours.len(), 2);
+        for (interval, cells) in &contours {
+            assert!(!cells.is_empty());
+            for &(x, y) in cells {
+                let radius = distance(x, y);
+                assert!(
+                    radius < *interval && radius >= interval - 1.0f64,
+                    "{}",
+                    radius
+                );
+            }
+
+            // Rings are symmetric about the center
+            for &(x, y) in cells {
+                assert!(cells.contains(&(20 - x, y)) && cells.contains(&(y, x)));
+            }
+        }
+
+        let outer_min = contours[1]
+            .1
+            .iter()
+            .map(|&(x, y)| distance(x, y))
+            .fold(f64::INFINITY, f64::min);
+        assert!(
+            contours[0]
+                .1
+                .iter()
+                .all(|&(x, y)| distance(x, y) < outer_min)
+        );
+    }
+
+    #[test]
+    fn deep_water_is_colder_and_thermocline_shifts_it() {
+        let mean_temperature = |map: &TopographicMap, depths: std::ops::Range<f64>| {
+            let temperatures: Vec<f64> = map
+                .cells()
+                .filter(|&(x, y, _)| {
+                    map.water_at(x, y)
+                        .is_some_and(|water| depths.contains(&water.depth().value()))
+                })
+                .filter_map(|(x, y, _)| map.temperature_at(x, y))
+                .collect();
+            assert!(!temperatures.is_empty());
+            temperatures.iter().sum::<f64>() / temperatures.len() as f64
+        };
+
+        let map = default_map();
+        assert!(mean_temperature(&map, 10.0f64..f64::MAX) < mean_temperature(&map, 0.0f64..3.0f64));
+        assert_eq!(map.temperature_at(11, 0), None);
+
+        let with_thermocline = |depth: f64| {
+            TopographicMapBuilder::new()
+                .seed(42)
+                .dimensions(96, 64)
+                .scale(0.12f64)
+                .thermocline_depth(depth)
+                .build()
+        };
+        let (shallow, deep) = (with_thermocline(3.0f64), with_thermocline(12.0f64));
+        assert!(
+            mean_temperature(&shallow, 5.0f64..10.0f64) < mean_temperature(&deep, 5.0f64..10.0f64)
+        );
+
+        // The transition is centered on the thermocline
+        let midpoint = (SURFACE_TEMPERATURE + DEEP_TEMPERATURE) / 2.0f64;
+        assert_eq!(Depth(3.0f64).temperature(3.0f64), midpoint);
+        assert_eq!(Depth(12.0f64).temperature(12.0f64), midpoint);
+    }
+
+    #[test]
+    fn lilypads_and_hydrilla_grow_at_their_depths() {
+        let range = |name: DepthRangeName| {
+            DEPTH_RANGES
+                .iter()
+                .find(|range| range.name == name)
+                .expect("Every range must be in the default table")
+        };
+        let (super_shallow, mid_depth) = (
+            range(DepthRangeName::SuperShallow),
+            range(DepthRangeName::MidDepth),
+        );
+
+        assert_eq!(
+            super_shallow.get_vegetation_rate(&Vegetation::Lilypads, false),
+            0.08f64
+        );
+        assert_eq!(
+            super_shallow.get_vegetation_rate(&Vegetation::Lilypads, true),
+            0.6f64
+        );
+        for adjacent in [false, true] {
+            assert!(
+                super_shallow.get_vegetation_rate(&Vegetation::Lilypads, adjacent)
+                    > mid_depth.get_vegetation_rate(&Vegetation::Lilypads, adjacent)
+            );
+            assert!(
+                mid_depth.get_vegetation_rate(&Vegetation::Hydrilla, adjacent)
+                    > super_shallow.get_vegetation_rate(&Vegetation::Hydrilla, adjacent)
+            );
+        }
+
+        let map = TopographicMap::new(42, 200, 200, 0.12f64);
+        let cells_with = |vegetation: Vegetation| -> Vec<DepthRangeName> {
+            map.cells()
+                .filter(|&(x, y, _)| {
+                    map.water_at(x, y)
+                        .is_some_and(|water| water.has_vegetation_type(&vegetation))
+                })
+                .filter_map(|(x, y, _)| map.range_name_at(x, y))
+                .collect()
+        };
+        let share = |names: &[DepthRangeName], name: DepthRangeName| {
+            names.iter().filter(|&&x| x == name).count() as f64 / names.len() as f64
+        };
+
+        let lilypads = cells_with(Vegetation::Lilypads);
+        let hydrilla = cells_with(Vegetation::Hydrilla);
+        assert!(!lilypads.is_empty() && !hydrilla.is_empty());
+        assert!(
+            share(&lilypads, DepthRangeName::SuperShallow)
+                > share(&hydrilla, DepthRangeName::SuperShallow)
+        );
+        assert!(
+            share(&hydrilla, DepthRangeName::MidDepth) > share(&lilypads, DepthRangeName::MidDepth)
+        );
+    }
+
+    #[test]
+    fn docks_only_stand_on_the_shore() {
+        let mut docks = 0;
+        for seed in 1..6 {
+            let map = TopographicMap::new(seed, 96, 64, 0.12f64);
+            for (x, y, _) in map.cells() {
+                let is_dock = map
+                    .water_at(x, y)
+                    .is_some_and(|water| water.structure() == Some(&Structure::Dock));
+                if !is_dock {
+                    continue;
+                }
+
+                docks += 1;
+                assert!(
+                    map.orthogonal_neighbors(x, y)
+                        .any(|(nx, ny)| map.water_at(nx, ny).is_none()),
+                    "Dock at ({}, {}) is off shore",
+                    x,
+                    y
+                );
+                assert!(matches!(
+                    map.range_name_at(x, y),
+                    Some(DepthRangeName::SuperShallow) | Some(DepthRangeName::Shallow)
+                ));
+            }
+        }
+
+        assert!(docks > 0);
+    }
+
+    #[test]
+    fn habitat_summary_counts_every_cell_once() {
+        for seed in 1..4 {
+            let map = TopographicMap::new(seed, 96, 64, 0.12f64);
+            let summary = map.habitat_summary();
+
+            assert_eq!(summary.land_cells + summary.water_cells, 96 * 64);
+            assert_eq!(summary.land_cells, land_cells(&map));
+            assert_eq!(summary.vegetation_counts.len(), Vegetation::ALL.len());
+            assert_eq!(summary.structure_counts.len(), Structure::ALL.len());
+            for &(_, count) in &summary.vegetation_counts {
+                assert!(count <= summary.water_cells);
+                assert!(summary.water_coverage(count) <= 100.0f64);
+            }
+            let structures: usize = summary
+                .structure_counts
+                .iter()
+                .map(|&(_, count)| count)
+                .sum();
+            assert!(structures <= summary.water_cells);
+            assert!(summary.to_string().starts_with("Land: "));
+        }
+
+        let dry = hand_map(&["##"]).habitat_summary();
+        assert_eq!((dry.land_cells, dry.water_cells), (2, 0));
+        assert_eq!(dry.water_coverage(0), 0.0f64);
+    }
+
+    #[test]
+    fn carving_joins_pools_across_a_land_bridge() {
+        let mut map = hand_map(&["######", "#2##3#", "#2##3#", "######"]);
+        assert_eq!(map.water_bodies().len(), 2);
+
+        // The bridge is two cells wide, so a shorter reach leaves it alone
+        assert_eq!(map.carve_channels(1), 0);
+        assert_eq!(map.water_bodies().len(), 2);
+
+        assert_eq!(map.carve_channels(2), 1);
+        assert_eq!(map.water_bodies().len(), 1);
+        assert_eq!(land_cells(&map), 18);
+        assert_eq!(map.carve_channels(2), 0);
+    }
+
+    #[test]
+    fn framed_string_has_border_and_axis_labels() {
+        let map = hand_map(&["#2", "g#", "3#"]);
+        let framed = map.to_framed_string();
+        let lines: Vec<&str> = framed.lines().collect();
+
+        assert_eq!(lines.len(), 3 + 3);
+        assert_eq!(lines[0], "  01");
+        assert_eq!(lines[1], " ┌──┐");
+        assert_eq!(lines[5], " └──┘");
+        for (y, line) in lines[2..5].iter().enumerate() {
+            assert!(line.starts_with(&format!("{}│", y)));
+            assert!(line.ends_with('│'));
+        }
+
+        let framed = default_map().to_framed_string();
+        assert_eq!(framed.lines().count(), 64 + 3);
+        assert!(!framed.contains('\u{1b}'));
+    }
+
+    #[test]
+    fn eastward_current_piles_mats_on_the_east_shore() {
+        let mut east = (0, 0);
+        let mut west = (0, 0);
+        for seed in 1..6 {
+            let map = TopographicMap::builder()
+                .seed(seed)
+                .dimensions(96, 64)
+                .scale(0.12f64)
+                .current(1.0f64, 0.0f64)
+                .build();
+            assert_eq!(map.current_direction(), (1.0f64, 0.0f64));
+
+            for (x, y, _) in map.cells() {
+                let Some(water) = map.water_at(x, y) else {
+                    continue;
+                };
+                let has_mats = water.has_vegetation_type(&Vegetation::Mats) as usize;
+                if x + 1 < map.width() && map.water_at(x + 1, y).is_none() {
+                    east.0 += has_mats;
+                    east.1 += 1;
+                }
+                if x > 0 && map.water_at(x - 1, y).is_none() {
+                    west.0 += has_mats;
+                    west.1 += 1;
+                }
+            }
+        }
+
+        let share = |(mats, cells): (usize, usize)| mats as f64 / cells as f64;
+        assert!(
+            share(east) > share(west),
+            "{} <= {}",
+            share(east),
+            share(west)
+        );
+    }
+
+    // Any change to generated output breaks these on purpose. Update them only
+    // when the change is meant to alter maps.
+    const GOLDEN_FINGERPRINTS: [(u32, usize, usize, f64, u64); 4] = [
+        (42, 96, 64, 0.12f64, 0x49efd2f94582cd37),
+        (1, 32, 24, 0.12f64, 0x068af259d0306a9f),
+        (7, 50, 50, 0.05f64, 0xe5da519c586d4213),
+        (12345, 17, 9, 0.3f64, 0x486f351ec968d66f),
+    ];
+
+    #[test]
+    fn generation_matches_golden_fingerprints() {
+        for (seed, width, height, scale, fingerprint) in GOLDEN_FINGERPRINTS {
+            assert_eq!(
+                TopographicMap::new(seed, width, height, scale).fingerprint(),
+                fingerprint,
+                "seed {} at {}x{} scale {}",
+                seed,
+                width,
+                height,
+                scale
+            );
+        }
+    }
+
+    #[test]
+    fn maps_compare_equal_only_with_matching_parameters() {
+        assert_eq!(default_map(), default_map());
+        assert_ne!(default_map(), TopographicMap::new(43, 96, 64, 0.12f64));
+        assert_ne!(default_map(), TopographicMap::new(42, 96, 64, 0.13f64));
+        assert_ne!(default_map(), TopographicMap::new(42, 64, 96, 0.12f64));
+
+        // One changed cell is enough to tell them apart
+        let mut grazed = default_map();
+        let (x, y) = grazed
+            .cells()
+            .find(|&(x, y, _)| grazed.vegetation_density_at(x, y) > 0.0f32)
+            .map(|(x, y, _)| (x, y))
+            .expect("Default map must have vegetation");
+        grazed.graze(x, y, 0.5f32);
+        assert_ne!(grazed, default_map());
+    }
+
+    #[test]
+    fn murky_water_thins_only_deep_vegetation() {
+        let with_clarity = |clarity: f64| {
+            TopographicMap::builder()
+                .seed(42)
+                .dimensions(96, 64)
+                .scale(0.12f64)
+                .clarity(clarity)
+                .build()
+        };
+        let rooted_share = |map: &TopographicMap, depths: std::ops::Range<f64>| {
+            let waters: Vec<&TopographicWaterRegion> = map
+                .cells()
+                .filter_map(|(x, y, _)| map.water_at(x, y))
+                .filter(|water| depths.contains(&water.depth().value()))
+                .collect();
+            let rooted = waters
+                .iter()
+                .filter(|water| water.vegetation().iter().any(|p| p.vegetation.is_rooted()))
+                .count();
+            rooted as f64 / waters.len() as f64
+        };
+
+        let (clear, murky) = (with_clarity(1.0f64), with_clarity(0.4f64));
+        // Local clarity strays up to CLARITY_NOISE_AMPLITUDE from the base
+        let murky_limit = (0.4f64 + CLARITY_NOISE_AMPLITUDE) * DEPTH_MAX;
+        let shallowest_limit = (0.4f64 - CLARITY_NOISE_AMPLITUDE) * DEPTH_MAX;
+
+        assert_eq!(rooted_share(&murky, murky_limit..DEPTH_MAX), 0.0f64);
+        assert!(rooted_share(&clear, murky_limit..DEPTH_MAX) > 0.0f64);
+
+        let shallow = 0.0f64..shallowest_limit;
+        let (clear_shallow, murky_shallow) = (
+            rooted_share(&clear, shallow.clone()),
+            rooted_share(&murky, shallow),
+        );
+        assert!(
+            (clear_shallow - murky_shallow).abs() < 0.05f64,
+            "{} vs {}",
+            clear_shallow,
+            murky_shallow
+        );
+
+        let clarity = murky.clarity_at(10, 10).expect("(10, 10) must be water");
+        assert!((0.0f64..=1.0f64).contains(&clarity));
+        assert_eq!(murky.clarity_at(11, 0), None);
+    }
+
+    #[test]
+    fn summer_leaves_more_vegetation_than_winter() {
+        let vegetated = |map: &TopographicMap| {
+            map.coverage(|region| match region {
+                TopographicRegion::Land(_) => false,
+                TopographicRegion::Water(water) => !water.vegetation().is_empty(),
+            })
+        };
+        let after = |season: Season| {
+            let mut map = default_map();
+            map.apply_season(season);
+            map
+        };
+        let before = vegetated(&default_map());
+
+        let (summer, winter) = (after(Season::Summer), after(Season::Winter));
+        assert!(vegetated(&summer) > before);
+        assert!(vegetated(&winter) < before);
+        assert_eq!(summer, after(Season::Summer));
+        assert_eq!(winter.land_masses(), default_map().land_masses());
+    }
+
+    #[test]
+    fn normalized_maps_cover_every_depth_range() {
+        let normalized = |land_threshold: f64| {
+            TopographicMap::builder()
+                .seed(42)
+                .dimensions(96, 64)
+                .scale(0.12f64)
+                .normalize(true)
+                .land_threshold(land_threshold)
+                .build()
+        };
+
+        let map = normalized(NOISE_LAND_MIN);
+        for name in DepthRangeName::ALL {
+            assert!(
+                map.cells()
+                    .any(|(x, y, _)| map.range_name_at(x, y) == Some(name)),
+                "{:?} is missing",
+                name
+            );
+        }
+
+        // The top sample sits exactly on the threshold and stays water
+        let map = normalized(1.0f64);
+        let waters: Vec<&TopographicWaterRegion> = map
+            .cells()
+            .filter_map(|(x, y, _)| map.water_at(x, y))
+            .collect();
+        assert!(!waters.is_empty());
+        assert!(
+            waters
+                .iter()
+                .all(|water| water.depth().value() == DEPTH_MIN)
+        );
+    }
+
+    #[test]
+    fn land_rises_away_from_the_beach_and_counts_in_fingerprints() {
+        let map = default_map();
+        let land: Vec<(usize, usize, f64)> = map
+            .cells()
+            .filter_map(|(x, y, _)| Some((x, y, map.elevation_at(x, y)?)))
+            .collect();
+        let on_beach = |x: usize, y: usize| {
+            map.orthogonal_neighbors(x, y)
+                .any(|(nx, ny)| map.water_at(nx, ny).is_some())
+        };
+        let mean = |beach: bool| {
+            let elevations: Vec<f64> = land
+                .iter()
+                .filter(|&&(x, y, _)| on_beach(x, y) == beach)
+                .map(|&(_, _, elevation)| elevation)
+                .collect();
+            elevations.iter().sum::<f64>() / elevations.len() as f64
+        };
+
+        assert!(mean(true) < mean(false));
+        let (x, y, _) = land
+            .iter()
+            .copied()
+            .max_by(|a, b| a.2.total_cmp(&b.2))
+            .expect("Default map must have land");
+        assert!(!on_beach(x, y));
+
+        // Flattening the peak alone changes the fingerprint
+        let mut flattened = map.clone();
+        flattened.data[(y * flattened.width) + x] =
+            TopographicRegion::Land(TopographicLandRegion::new(0.01f64));
+        assert_ne!(flattened.fingerprint(), map.fingerprint());
+    }
+
+    #[test]
+    fn truecolor_shades_exact_depths() {
+        let map = hand_map(&["12#"]);
+
+        assert_eq!(map.range_name_at(0, 0), map.range_name_at(1, 0));
+        assert_ne!(map.shade_at(0, 0), map.shade_at(1, 0));
+        assert_eq!(map.shade_at(2, 0), Some(Rgb(HEIGHTMAP_LAND)));
+        assert_eq!(map.shade_at(3, 0), None);
+        assert_eq!(
+            map.shade_at(1, 0),
+            Some(map.water_at(1, 0).unwrap().depth().heightmap_pixel())
+        );
+    }
+
+    #[test]
+    fn timed_generation_matches_plain_generation() {
+        let (map, timings) = TopographicMap::new_timed(42, 96, 64, 0.12f64);
+
+        assert_eq!(map, default_map());
+        assert_eq!(timings.total(), timings.noise + timings.features);
+        assert!(timings.features > Duration::ZERO);
+    }
+
+    #[test]
+    fn deepest_and_shallowest_cells_bound_every_water_cell() {
+        let map = default_map();
+        let (x, y, deepest) = map.deepest_cell().expect("Default map must have water");
+        let (sx, sy, shallowest) = map
+            .shallowest_water_cell()
+            .expect("Default map must have water");
+
+        assert_eq!(
+            map.water_at(x, y).map(|water| water.depth().value()),
+            Some(deepest)
+        );
+        assert_eq!(
+            map.water_at(sx, sy).map(|water| water.depth().value()),
+            Some(shallowest)
+        );
+        for (cx, cy, _) in map.cells() {
+            if let Some(water) = map.water_at(cx, cy) {
+                assert!(water.depth().value() <= deepest);
+                assert!(water.depth().value() >= shallowest);
+            }
+        }
+
+        // Ties go to the first cell in row-major order
+        let tied = hand_map(&["#33", "131"]);
+        assert_eq!(tied.deepest_cell(), Some((1, 0, 3.0f64)));
+        assert_eq!(tied.shallowest_water_cell(), Some((0, 1, 1.0f64)));
+        assert_eq!(hand_map(&["##"]).deepest_cell(), None);
+    }
+
+    #[test]
+    fn rotation_swaps_scales_with_the_axes() {
+        let transitions = |map: &TopographicMap, (dx, dy): (usize, usize)| {
+            map.cells()
+                .filter(|&(x, y, _)| x + dx < map.width() && y + dy < map.height())
+                .filter(|&(x, y, _)| map.range_name_at(x, y) != map.range_name_at(x + dx, y + dy))
+                .count()
+        };
+        let map = TopographicMap::builder()
+            .dimensions(64, 48)
+            .scale_x(0.03f64)
+            .scale_y(0.3f64)
+            .build();
+
+        // Noise stretched along x changes less from column to column
+        assert!(transitions(&map, (1, 0)) < transitions(&map, (0, 1)));
+
+        let rotated = map.rotate_cw();
+        assert_eq!(rotated.scale, (0.3f64, 0.03f64));
+        assert_eq!(transitions(&rotated, (1, 0)), transitions(&map, (0, 1)));
+        assert_eq!(transitions(&rotated, (0, 1)), transitions(&map, (1, 0)));
+    }
+
+    #[test]
+    fn fitted_string_stays_within_the_terminal() {
+        let map = TopographicMap::new(42, 200, 200, 0.12f64);
+        let text = map.to_fitted_string(80, 50);
+        let rows: Vec<&str> = text.lines().collect();
+
+        assert!(rows.len() <= 50);
+        assert!(rows.iter().all(|row| row.chars().count() <= 80));
+        // Square maps stay square, shrunk by whichever limit is tighter
+        assert_eq!(rows.len(), 50);
+        assert!(rows.iter().all(|row| row.chars().count() == 50));
+
+        assert_eq!(
+            default_map().to_fitted_string(96, 64),
+            default_map().to_plain_string()
+        );
+        assert_eq!(map.to_fitted_string(0, 50), "");
+    }
+
+    // Two ranges split halfway down, with mats only in the shallow or only in
+    // the deep one. Mats aren't rooted, so light doesn't limit them.
+    fn two_range_table(shallow_mats: bool) -> Vec<DepthRange> {
+        const MATS: &[VegetationRate] = &[VegetationRate::new(Vegetation::Mats, 1.0f64, 1.0f64)];
+        let split = (DEPTH_MIN + DEPTH_MAX) / 2.0f64;
+        let rates = |mats: bool| {
+            if mats {
+                Cow::Borrowed(MATS)
+            } else {
+                Cow::Borrowed(&[][..])
+            }
+        };
+
+        vec![
+            DepthRange {
+                min: DEPTH_MIN,
+                max: split,
+                vegetation_rates: rates(shallow_mats),
+                structure_rates: Cow::Borrowed(&[]),
+                name: DepthRangeName::Shallow,
+            },
+            DepthRange {
+                min: split,
+                max: DEPTH_MAX,
+                vegetation_rates: rates(!shallow_mats),
+                structure_rates: Cow::Borrowed(&[]),
+                name: DepthRangeName::Deep,
+            },
+        ]
+    }
+
+    #[test]
+    fn vegetation_follows_a_custom_two_range_table() {
+        let split = (DEPTH_MIN + DEPTH_MAX) / 2.0f64;
+
+        // Share of water cells above and below the split that grew mats
+        let mat_shares = |map: &TopographicMap| {
+            let (mut shallow, mut deep) = ((0, 0), (0, 0));
+            for (_, _, region) in map.cells() {
+                let TopographicRegion::Water(water) = region else {
+                    continue;
+                };
+                let side = if water.depth().value() < split {
+                    &mut shallow
+                } else {
+                    &mut deep
+                };
+                side.0 += water.has_vegetation_type(&Vegetation::Mats) as usize;
+                side.1 += 1;
+            }
+            (
+                shallow.0 as f64 / shallow.1 as f64,
+                deep.0 as f64 / deep.1 as f64,
+            )
+        };
+
+        for shallow_mats in [true, false] {
+            let map = TopographicMap::builder()
+                .depth_ranges(two_range_table(shallow_mats))
+                .build();
+            let (shallow, deep) = mat_shares(&map);
+            let (grown, bare) = if shallow_mats {
+                (shallow, deep)
+            } else {
+                (deep, shallow)
+            };
+
+            assert!(grown > 0.7, "{} of the matted range grew mats", grown);
+            assert!(bare < 0.05, "{} of the bare range grew mats", bare);
+
+            for (x, y, region) in map.cells() {
+                let TopographicRegion::Water(water) = region else {
+                    continue;
+                };
+                let name = map.range_name_at(x, y).unwrap();
+                assert!(matches!(
+                    name,
+                    DepthRangeName::Shallow | DepthRangeName::Deep
+                ));
+                assert!(water.describe_in(map.depth_ranges()).contains(name.label()));
+            }
+            for symbol in [
+                DepthRangeName::SuperShallow.symbol(),
+                DepthRangeName::MidDepth.symbol(),
+            ] {
+                assert!(!map.to_plain_string().contains(symbol));
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip_preserves_a_custom_depth_table() {
+        let map = TopographicMap::builder()
+            .depth_ranges(two_range_table(true))
+            .build();
+
+        let json = serde_json::to_string(&map).unwrap();
+        let loaded: TopographicMap = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(loaded, map);
+        assert_eq!(loaded.depth_ranges(), two_range_table(true).as_slice());
+    }
+
+    #[test]
+    fn coverage_of_land_and_water_sums_to_one() {
+        let map = default_map();
+
+        let land = map.coverage(|region| region.is_land());
+        let water = map.coverage(|region| !region.is_land());
+
+        assert_eq!(map.coverage(|_| true), 1.0f64);
+        assert!(land > 0.0f64 && water > 0.0f64);
+        assert!((land + water - 1.0f64).abs() < 1e-12);
+        assert_eq!(land, land_cells(&map) as f64 / map.cells().count() as f64);
+    }
+
+    #[test]
+    fn linear_ramp_has_constant_slope() {
+        // Depth rises by 1.0 per column, with a land bank on the left
+        let map = hand_map(&["#12345678", "#12345678", "#12345678", "#12345678"]);
+
+        let slopes = map.slope_map();
+
+        assert_eq!(slopes.len(), map.width() * map.height());
+        for (i, slope) in slopes.iter().enumerate() {
+            let expected = if i % map.width() == 0 { 0.0f64 } else { 1.0f64 };
+            assert!(
+                (slope - expected).abs() < 1e-9,
+                "cell {} has slope {}",
+                i,
+                slope
+            );
+        }
+    }
+
+    #[test]
+    fn summer_grows_no_rooted_vegetation_past_the_light_limit() {
+        let mut map = TopographicMap::builder()
+            .seed(42)
+            .dimensions(96, 64)
+            .scale(0.12f64)
+            .clarity(0.4f64)
+            .build();
+        let before = map.clone();
+        map.apply_season(Season::Summer);
+
+        let mut colonized = 0;
+        for (x, y, _) in map.cells() {
+            let Some(water) = map.water_at(x, y) else {
+                continue;
+            };
+            if before.water_at(x, y).unwrap().vegetation().is_empty() {
+                colonized += !water.vegetation().is_empty() as usize;
+            }
+            let light_limit = water.clarity() * DEPTH_MAX;
+            if water.depth().value() > light_limit {
+                assert!(
+                    water.vegetation().iter().all(|p| !p.vegetation.is_rooted()),
+                    "({}, {}) at depth {} past {}",
+                    x,
+                    y,
+                    water.depth().value(),
+                    light_limit
+                );
+            }
+        }
+        assert!(colonized > 0);
+    }
 }